@@ -0,0 +1,218 @@
+use std::net::IpAddr;
+
+use crate::block_list::{
+    BlockListReport, BlockListSource, Explanation, ListDiff, ListFormat, Snapshot,
+};
+use crate::http::percent_encode;
+use crate::policy::Pause;
+use crate::OpaqueError;
+
+use super::ADMIN_HEADER;
+
+/// Talks to the admin api of a running instance. This is what backs the cli commands.
+pub struct AdminClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl AdminClient {
+    pub fn new(admin_addr: &str) -> Self {
+        AdminClient {
+            base_url: format!("http://{}", admin_addr),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn lists(&self) -> Result<Vec<BlockListReport>, OpaqueError> {
+        let url = format!("{}/lists", self.base_url);
+        Ok(check(self.client.get(url).send().await?)
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Adds a list, or changes the settings given of the existing list with the name. New lists
+    /// need a location.
+    pub async fn add_list(
+        &self,
+        name: &str,
+        location: Option<&str>,
+        format: Option<ListFormat>,
+        category: Option<&str>,
+        refresh_interval_secs: Option<u64>,
+    ) -> Result<BlockListSource, OpaqueError> {
+        let mut url = format!("{}/lists?list={}", self.base_url, percent_encode(name));
+        if let Some(location) = location {
+            url.push_str(&format!("&location={}", percent_encode(location)));
+        }
+        if let Some(format) = format {
+            url.push_str(&format!("&format={}", format));
+        }
+        if let Some(category) = category {
+            url.push_str(&format!("&category={}", percent_encode(category)));
+        }
+        if let Some(interval) = refresh_interval_secs {
+            url.push_str(&format!("&refresh_interval_secs={}", interval));
+        }
+        Ok(check(
+            self.client
+                .post(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await?)
+    }
+
+    pub async fn remove_list(&self, name: &str) -> Result<(), OpaqueError> {
+        let url = format!("{}/lists?list={}", self.base_url, percent_encode(name));
+        check(
+            self.client
+                .delete(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Disabled lists neither block nor get updated.
+    pub async fn set_list_enabled(&self, name: &str, enabled: bool) -> Result<(), OpaqueError> {
+        let action = if enabled { "enable" } else { "disable" };
+        let url = format!("{}/{}?list={}", self.base_url, action, percent_encode(name));
+        check(
+            self.client
+                .post(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn explain(
+        &self,
+        domain: &str,
+        client: Option<IpAddr>,
+    ) -> Result<Explanation, OpaqueError> {
+        let mut url = format!(
+            "{}/explain?domain={}",
+            self.base_url,
+            percent_encode(domain)
+        );
+        if let Some(client) = client {
+            url.push_str(&format!("&client={}", percent_encode(&client.to_string())));
+        }
+        Ok(check(self.client.get(url).send().await?)
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Pauses blocking for the given duration (i.e. `5m`), for everyone or a single group.
+    pub async fn pause(&self, duration: &str, group: Option<&str>) -> Result<Pause, OpaqueError> {
+        let mut url = format!(
+            "{}/pause?duration={}",
+            self.base_url,
+            percent_encode(duration)
+        );
+        if let Some(group) = group {
+            url.push_str(&format!("&group={}", percent_encode(group)));
+        }
+        Ok(check(
+            self.client
+                .post(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await?)
+    }
+
+    pub async fn resume(&self, group: Option<&str>) -> Result<(), OpaqueError> {
+        let mut url = format!("{}/resume", self.base_url);
+        if let Some(group) = group {
+            url.push_str(&format!("?group={}", percent_encode(group)));
+        }
+        check(
+            self.client
+                .post(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn snapshots(&self, list: &str) -> Result<Vec<Snapshot>, OpaqueError> {
+        let url = format!("{}/snapshots?list={}", self.base_url, percent_encode(list));
+        Ok(check(self.client.get(url).send().await?)
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// How the list changed with the update that produced the snapshot, the one in use if none
+    /// is given.
+    pub async fn diff(&self, list: &str, snapshot: Option<&str>) -> Result<ListDiff, OpaqueError> {
+        let mut url = format!("{}/diff?list={}", self.base_url, percent_encode(list));
+        if let Some(snapshot) = snapshot {
+            url.push_str(&format!("&snapshot={}", percent_encode(snapshot)));
+        }
+        Ok(check(self.client.get(url).send().await?)
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Rolls the list back to the given snapshot, or to the one before the snapshot in use.
+    pub async fn rollback(&self, list: &str, to: Option<&str>) -> Result<Snapshot, OpaqueError> {
+        let mut url = format!("{}/rollback?list={}", self.base_url, percent_encode(list));
+        if let Some(to) = to {
+            url.push_str(&format!("&to={}", percent_encode(to)));
+        }
+        Ok(check(
+            self.client
+                .post(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?
+        .json()
+        .await?)
+    }
+
+    /// Refreshes the given list, or every list, without waiting for it to be due.
+    pub async fn refresh(&self, list: Option<&str>) -> Result<(), OpaqueError> {
+        let mut url = format!("{}/refresh", self.base_url);
+        if let Some(list) = list {
+            url.push_str(&format!("?list={}", percent_encode(list)));
+        }
+        check(
+            self.client
+                .post(url)
+                .header(ADMIN_HEADER, "1")
+                .send()
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Turns error responses into errors carrying the message the api gave.
+async fn check(response: reqwest::Response) -> Result<reqwest::Response, OpaqueError> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(response.text().await?.into())
+    }
+}
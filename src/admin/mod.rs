@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::http::{Request, Response};
+use crate::{LongRunningTaskType, OpaqueError, QueryService, Ready};
+
+mod client;
+mod routes;
+
+pub use client::*;
+
+/// Header requests changing anything need to carry. Browsers do not send custom headers to other
+/// origins without asking first (which is not answered), so web pages cannot make such requests.
+pub const ADMIN_HEADER: &str = "x-rustle-admin";
+
+/// Provider for the task serving the admin api.
+/// The api speaks json over plain http and is meant to be reachable from the local host only
+/// (which is also what the cli talks to). Requests must name the admin address as their host,
+/// which turns away web pages that got their own domain resolved to it (DNS rebinding).
+pub async fn serve(
+    addr: &str,
+    query_service: Arc<QueryService<Ready>>,
+) -> Result<LongRunningTaskType, OpaqueError> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    // TODO: log this
+    println!("Admin api listening on {}", local_addr);
+    let hosts = Arc::new([addr.to_string(), local_addr.to_string()]);
+
+    let admin_task = tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            let query_service = query_service.clone();
+            let hosts = hosts.clone();
+
+            tokio::spawn(async move {
+                let response = match Request::read_from(&mut stream).await {
+                    Ok(request) => match refusal(&request, hosts.as_slice()) {
+                        Some(refusal) => refusal,
+                        None => routes::handle(request, &query_service).await,
+                    },
+                    Err(e) => Response::text(400, &e.to_string()),
+                };
+                response.write_to(&mut stream).await?;
+
+                Ok::<(), OpaqueError>(())
+            });
+        }
+        // TODO: Add shutdown routine
+        #[allow(unreachable_code)]
+        Ok::<(), OpaqueError>(())
+    });
+
+    Ok(admin_task)
+}
+
+/// Why the request is not served, if it is not.
+fn refusal(request: &Request, hosts: &[String]) -> Option<Response> {
+    match request.headers.get("host") {
+        Some(host) if hosts.contains(host) => {}
+        _ => return Some(Response::text(403, "Unexpected host")),
+    }
+    if request.method != "GET" && !request.headers.contains_key(ADMIN_HEADER) {
+        let message = format!("Missing {} header", ADMIN_HEADER);
+        return Some(Response::text(403, &message));
+    }
+    None
+}
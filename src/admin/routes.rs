@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::http::{Request, Response};
+use crate::policy::{parse_duration, MAX_PAUSE};
+use crate::{BlockListSource, QueryService, Ready, SourceLocation};
+
+pub(super) async fn handle(request: Request, query_service: &QueryService<Ready>) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/lists") => Response::json(200, &query_service.block_lists().await),
+        ("POST", "/lists") => {
+            let Some(list) = request.query.get("list") else {
+                return Response::text(400, "Missing list");
+            };
+            let existing = query_service.source(list).await;
+            let source = match source_from_query(list, existing, &request.query) {
+                Ok(source) => source,
+                Err(e) => return Response::text(400, &e),
+            };
+            match query_service.upsert_source(source.clone()).await {
+                Ok(()) => Response::json(200, &source),
+                Err(e) => Response::text(400, &e),
+            }
+        }
+        ("DELETE", "/lists") => {
+            let Some(list) = request.query.get("list") else {
+                return Response::text(400, "Missing list");
+            };
+            if query_service.remove_source(list).await {
+                Response::text(200, "Removed")
+            } else {
+                Response::text(404, "No such list")
+            }
+        }
+        ("POST", path @ ("/enable" | "/disable")) => {
+            let Some(list) = request.query.get("list") else {
+                return Response::text(400, "Missing list");
+            };
+            let enabled = path == "/enable";
+            if query_service.set_source_enabled(list, enabled).await {
+                Response::text(200, if enabled { "Enabled" } else { "Disabled" })
+            } else {
+                Response::text(404, "No such list")
+            }
+        }
+        ("GET", "/explain") => {
+            let Some(domain) = request.query.get("domain") else {
+                return Response::text(400, "Missing domain");
+            };
+            let client = match request.query.get("client").map(|client| client.parse()) {
+                Some(Ok(client)) => Some(client),
+                Some(Err(_)) => return Response::text(400, "Invalid client address"),
+                None => None,
+            };
+            Response::json(200, &query_service.explain(domain, client).await)
+        }
+        ("GET", "/unblock-requests") => {
+            Response::json(200, &query_service.unblock_requests().await)
+        }
+        ("GET", "/pauses") => Response::json(200, &query_service.pauses().await),
+        ("POST", "/pause") => {
+            let duration = match request.query.get("duration").map(|d| parse_duration(d)) {
+                Some(Ok(duration)) if duration > MAX_PAUSE => {
+                    let max_hours = MAX_PAUSE.as_secs() / 60 / 60;
+                    let message = format!("Pauses last at most {}h", max_hours);
+                    return Response::text(400, &message);
+                }
+                Some(Ok(duration)) => duration,
+                Some(Err(e)) => return Response::text(400, &e),
+                None => return Response::text(400, "Missing duration"),
+            };
+            let group = request.query.get("group").cloned();
+            match query_service.pause(group, duration).await {
+                Ok(pause) => Response::json(200, &pause),
+                Err(e) => Response::text(404, &e),
+            }
+        }
+        ("POST", "/resume") => {
+            let group = request.query.get("group").map(String::as_str);
+            if query_service.resume(group).await {
+                Response::text(200, "Resumed")
+            } else {
+                Response::text(404, "Not paused")
+            }
+        }
+        ("POST", "/refresh") => {
+            let list = request.query.get("list").map(String::as_str);
+            if query_service.refresh(list).await {
+                Response::text(200, "Refresh scheduled")
+            } else {
+                Response::text(404, "No such list")
+            }
+        }
+        ("GET", "/snapshots") => {
+            let Some(list) = request.query.get("list") else {
+                return Response::text(400, "Missing list");
+            };
+            match query_service.snapshots(list).await {
+                Ok(snapshots) => Response::json(200, &snapshots),
+                Err(e) => Response::text(404, &e),
+            }
+        }
+        ("GET", "/diff") => {
+            let Some(list) = request.query.get("list") else {
+                return Response::text(400, "Missing list");
+            };
+            let snapshot = request.query.get("snapshot").map(String::as_str);
+            match query_service.diff(list, snapshot).await {
+                Ok(diff) => Response::json(200, &diff),
+                Err(e) => Response::text(404, &e),
+            }
+        }
+        ("POST", "/rollback") => {
+            let Some(list) = request.query.get("list") else {
+                return Response::text(400, "Missing list");
+            };
+            let to = request.query.get("to").map(String::as_str);
+            match query_service.rollback(list, to).await {
+                Ok(snapshot) => Response::json(200, &snapshot),
+                Err(e) => Response::text(404, &e),
+            }
+        }
+        _ => Response::not_found(),
+    }
+}
+
+/// The source as configured by the query: the existing source with the given settings changed,
+/// or a new one if there is none by that name, which needs a location.
+fn source_from_query(
+    name: &str,
+    existing: Option<BlockListSource>,
+    query: &HashMap<String, String>,
+) -> Result<BlockListSource, String> {
+    let location = query.get("location").cloned().map(SourceLocation::from);
+    let mut source = match existing {
+        Some(source) => source,
+        None => BlockListSource {
+            name: name.to_string(),
+            location: location.clone().ok_or("Missing location")?,
+            category: String::new(),
+            ..BlockListSource::default()
+        },
+    };
+    if let Some(location) = location {
+        source.location = location;
+    }
+    if let Some(format) = query.get("format") {
+        source.format = format.parse()?;
+    }
+    if let Some(category) = query.get("category") {
+        source.category = category.clone();
+    }
+    if let Some(interval) = query.get("refresh_interval_secs") {
+        let interval = interval
+            .parse()
+            .map_err(|_| format!("Invalid refresh interval {}", interval))?;
+        source.refresh_interval_secs = Some(interval);
+    }
+    if let Some(enabled) = query.get("enabled") {
+        source.enabled = enabled
+            .parse()
+            .map_err(|_| format!("Invalid enabled flag {}", enabled))?;
+    }
+    Ok(source)
+}
@@ -0,0 +1,140 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An address range in CIDR notation (i.e. `192.0.2.0/24` or `2001:db8::/32`).
+/// A bare address is taken as a range covering only itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// IPv4 addresses mapped into IPv6 (`::ffff:192.0.2.1`) are also taken as what they map to.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.contains_exactly(ip) || self.contains_exactly(&ip.to_canonical())
+    }
+
+    fn contains_exactly(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("{} is not an ip address", addr))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .ok_or(format!("{} is not a valid prefix length", prefix_len))?,
+            None => max_prefix_len,
+        };
+        Ok(IpCidr { addr, prefix_len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ranges_and_bare_addresses() {
+        assert_eq!(cidr("192.0.2.0/24").to_string(), "192.0.2.0/24");
+        assert_eq!(cidr("192.0.2.1").to_string(), "192.0.2.1/32");
+        assert_eq!(cidr("2001:db8::1").to_string(), "2001:db8::1/128");
+        assert_eq!(cidr("::/0").to_string(), "::/0");
+    }
+
+    #[test]
+    fn refuses_bad_prefixes() {
+        for s in [
+            "192.0.2.0/33",
+            "2001:db8::/129",
+            "192.0.2.0/",
+            "192.0.2.0/-1",
+            "192.0.2.0/x",
+            "192.0.2.0/24/8",
+            "192.0.2/24",
+            "example.com",
+        ] {
+            assert!(s.parse::<IpCidr>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn contains_addresses_within_the_prefix() {
+        let range = cidr("192.0.2.0/24");
+        assert!(range.contains(&ip("192.0.2.0")));
+        assert!(range.contains(&ip("192.0.2.255")));
+        assert!(!range.contains(&ip("192.0.3.0")));
+        assert!(!range.contains(&ip("2001:db8::1")));
+
+        let range = cidr("2001:db8::/32");
+        assert!(range.contains(&ip("2001:db8:ffff::1")));
+        assert!(!range.contains(&ip("2001:db9::1")));
+        assert!(!range.contains(&ip("192.0.2.1")));
+    }
+
+    #[test]
+    fn full_length_prefixes_contain_a_single_address() {
+        assert!(cidr("192.0.2.1/32").contains(&ip("192.0.2.1")));
+        assert!(!cidr("192.0.2.1/32").contains(&ip("192.0.2.2")));
+        assert!(cidr("2001:db8::1/128").contains(&ip("2001:db8::1")));
+        assert!(!cidr("2001:db8::1/128").contains(&ip("2001:db8::2")));
+    }
+
+    #[test]
+    fn empty_prefixes_contain_their_whole_family() {
+        assert!(cidr("0.0.0.0/0").contains(&ip("203.0.113.7")));
+        assert!(cidr("0.0.0.0/0").contains(&ip("255.255.255.255")));
+        assert!(!cidr("0.0.0.0/0").contains(&ip("2001:db8::1")));
+        assert!(cidr("::/0").contains(&ip("2001:db8::1")));
+        assert!(!cidr("::/0").contains(&ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn takes_mapped_addresses_as_ipv4() {
+        assert!(cidr("192.0.2.0/24").contains(&ip("::ffff:192.0.2.1")));
+        assert!(!cidr("192.0.2.0/24").contains(&ip("::ffff:192.0.3.1")));
+        assert!(cidr("::ffff:0:0/96").contains(&ip("::ffff:192.0.2.1")));
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::list_rules::ListRules;
+
+/// How a list changed with an update: the entries (domains, `@@` exceptions and address ranges)
+/// it gained and lost.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListDiff {
+    pub list: String,
+    pub updated_at: DateTime<Local>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// The counts of a diff, what is kept in the status of a list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl ListDiff {
+    pub fn between(
+        list: &str,
+        updated_at: DateTime<Local>,
+        previous: &ListRules,
+        current: &ListRules,
+    ) -> Self {
+        let previous = previous.entries();
+        let current = current.entries();
+        let sorted = |entries: HashSet<&String>| {
+            let mut entries: Vec<String> = entries.into_iter().cloned().collect();
+            entries.sort();
+            entries
+        };
+        ListDiff {
+            list: list.to_string(),
+            updated_at,
+            added: sorted(current.difference(&previous).collect()),
+            removed: sorted(previous.difference(&current).collect()),
+        }
+    }
+
+    pub fn summary(&self) -> DiffSummary {
+        DiffSummary {
+            added: self.added.len(),
+            removed: self.removed.len(),
+        }
+    }
+}
+
+impl std::fmt::Display for ListDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} updated {}: {} added, {} removed",
+            self.list,
+            self.updated_at.format("%Y-%m-%d %H:%M"),
+            self.added.len(),
+            self.removed.len()
+        )?;
+        for entry in &self.added {
+            write!(f, "\n+ {}", entry)?;
+        }
+        for entry in &self.removed {
+            write!(f, "\n- {}", entry)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use super::rule_set::RuleSet;
+use super::source::SourceLocation;
+
+/// Writes come in bursts of events. Changes are picked up this long after the first one, so the
+/// rest of the burst is taken in along with it and a half written file is not read.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Watches the files of local lists (or hosts files) with inotify, so edits to them are picked up
+/// right away rather than when the lists are due. The directories of the files are watched rather
+/// than the files themselves, since editors tend to replace a file on save instead of writing to
+/// it.
+pub struct ListFiles {
+    watcher: Option<RecommendedWatcher>,
+    changes: UnboundedReceiver<PathBuf>,
+    dirs: HashSet<PathBuf>,
+    /// The lists read from each watched file, keyed by the path events come with.
+    lists: HashMap<PathBuf, Vec<String>>,
+    /// Lists whose file changed, kept here so they are not lost if waiting is cut short.
+    pending: HashSet<String>,
+}
+
+impl Default for ListFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListFiles {
+    /// Without a watcher (i.e. when out of inotify instances) local lists are only reloaded when
+    /// they are due.
+    pub fn new() -> Self {
+        let (sender, changes) = unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Reading the lists shows up as access too.
+            let changed = match event.kind {
+                EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+                EventKind::Access(_) => false,
+                _ => true,
+            };
+            if changed {
+                for path in event.paths {
+                    _ = sender.send(path);
+                }
+            }
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                // TODO: log this
+                println!("Local lists cannot be watched for changes: {}", e);
+                None
+            }
+        };
+        ListFiles {
+            watcher,
+            changes,
+            dirs: HashSet::new(),
+            lists: HashMap::new(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Names and paths of the enabled lists read from local files.
+    pub fn watched(rule_set: &RuleSet) -> Vec<(String, PathBuf)> {
+        rule_set
+            .lists()
+            .iter()
+            .filter(|list| list.source.enabled)
+            .filter_map(|list| match &list.source.location {
+                SourceLocation::Path(path) => Some((list.source.name.clone(), path.clone())),
+                SourceLocation::Url(_) => None,
+            })
+            .collect()
+    }
+
+    /// Watches the files of the given lists, and only those.
+    pub async fn watch(&mut self, watched: Vec<(String, PathBuf)>) {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        let mut dirs = HashSet::new();
+        let mut lists: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        for (name, path) in watched {
+            let Some(file_name) = path.file_name() else {
+                continue;
+            };
+            let dir = match path.parent() {
+                Some(dir) if dir != Path::new("") => dir,
+                _ => Path::new("."),
+            };
+            // Events carry the path of the watched directory, so it is watched by a path that
+            // does not change with the working directory.
+            let Ok(dir) = tokio::fs::canonicalize(dir).await else {
+                continue;
+            };
+            if !self.dirs.contains(&dir) && !dirs.contains(&dir) {
+                if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    // TODO: log this
+                    println!("{} cannot be watched for changes: {}", dir.display(), e);
+                    continue;
+                }
+            }
+            lists.entry(dir.join(file_name)).or_default().push(name);
+            dirs.insert(dir);
+        }
+        for dir in self.dirs.difference(&dirs) {
+            _ = watcher.unwatch(dir);
+        }
+        self.dirs = dirs;
+        self.lists = lists;
+    }
+
+    /// Waits for files of watched lists to change and returns the names of their lists.
+    pub async fn changed(&mut self) -> Vec<String> {
+        while self.pending.is_empty() {
+            let Some(path) = self.changes.recv().await else {
+                // There is no watcher to report changes.
+                return std::future::pending().await;
+            };
+            self.add_pending(&path);
+        }
+        tokio::time::sleep(SETTLE_TIME).await;
+        while let Ok(path) = self.changes.try_recv() {
+            self.add_pending(&path);
+        }
+        self.pending.drain().collect()
+    }
+
+    fn add_pending(&mut self, path: &Path) {
+        if let Some(lists) = self.lists.get(path) {
+            self.pending.extend(lists.iter().cloned());
+        }
+    }
+}
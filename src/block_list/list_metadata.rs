@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The header is expected within the first lines of a list.
+const MAX_HEADER_LINES: usize = 100;
+
+/// Lists asking to be refreshed more often than this are refreshed hourly.
+const MIN_EXPIRES_SECS: u64 = 60 * 60;
+
+/// What a list says about itself in its header comments, i.e. `! Title: EasyList` or
+/// `# Expires: 4 days (update frequency)`.
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    /// How long the list stays current, which is how often it is refreshed unless the source
+    /// says otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_secs: Option<u64>,
+    /// Where the next differential update of the list is published, relative to its url.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_path: Option<String>,
+    /// How often to check for differential updates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_expires_secs: Option<u64>,
+}
+
+impl ListMetadata {
+    pub fn expires(&self) -> Option<Duration> {
+        self.expires_secs.map(Duration::from_secs)
+    }
+
+    /// How often the list asks to be checked for updates: as often as its diffs expire if it
+    /// publishes diffs, as often as it expires otherwise.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        match &self.diff_path {
+            Some(_) => self
+                .diff_expires_secs
+                .map(Duration::from_secs)
+                .or(self.expires()),
+            None => self.expires(),
+        }
+    }
+
+    /// Reads the header of a list. It ends with the first line that is not a comment.
+    pub fn parse(content: &str) -> Self {
+        let mut metadata = ListMetadata::default();
+        for line in content.lines().take(MAX_HEADER_LINES) {
+            let line = line.trim();
+            // Adblock lists start with a `[Adblock Plus 2.0]` line.
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix('!').or_else(|| line.strip_prefix('#')) else {
+                break;
+            };
+            let Some((key, value)) = comment.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match key.trim().to_ascii_lowercase().as_str() {
+                "title" => metadata.title = Some(value.to_string()),
+                "version" => metadata.version = Some(value.to_string()),
+                "homepage" => metadata.homepage = Some(value.to_string()),
+                "expires" => metadata.expires_secs = parse_expires(value),
+                "diff-path" => metadata.diff_path = Some(value.to_string()),
+                "diff-expires" => metadata.diff_expires_secs = parse_expires(value),
+                _ => {}
+            }
+        }
+        metadata
+    }
+}
+
+/// Parses values like `4 days (update frequency)`, `12 hours` or `1d` into seconds.
+fn parse_expires(value: &str) -> Option<u64> {
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let amount: u64 = value[..digits].parse().ok()?;
+    let rest = value[digits..].trim_start();
+    let unit_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    // Anything after the unit is a remark, i.e. `(update frequency)`, but `4.5 days` is not 4.
+    let (unit, remark) = rest.split_at(unit_len);
+    if !remark.is_empty() && !remark.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let unit_secs = match unit.to_ascii_lowercase().as_str() {
+        // A bare number is in days, as Adblock Plus reads it.
+        "" | "d" | "day" | "days" => 60 * 60 * 24,
+        "h" | "hour" | "hours" => 60 * 60,
+        _ => return None,
+    };
+    Some(amount.checked_mul(unit_secs)?.max(MIN_EXPIRES_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 60 * 60 * 24;
+
+    #[test]
+    fn parses_expiry_periods() {
+        assert_eq!(parse_expires("4 days"), Some(4 * DAY));
+        assert_eq!(parse_expires("1 day"), Some(DAY));
+        assert_eq!(
+            parse_expires("12 hours (update frequency)"),
+            Some(12 * 60 * 60)
+        );
+        assert_eq!(parse_expires("6h"), Some(6 * 60 * 60));
+        assert_eq!(parse_expires("2d"), Some(2 * DAY));
+        // Adblock Plus reads bare numbers as days.
+        assert_eq!(parse_expires("5"), Some(5 * DAY));
+    }
+
+    #[test]
+    fn clamps_short_expiry_periods() {
+        assert_eq!(parse_expires("0 hours"), Some(MIN_EXPIRES_SECS));
+        assert_eq!(parse_expires("0"), Some(MIN_EXPIRES_SECS));
+    }
+
+    #[test]
+    fn ignores_garbage() {
+        for value in [
+            "",
+            "soon",
+            "days",
+            "4 weeks",
+            "-1 days",
+            "4.5 days",
+            "99999999999999999999 days",
+            "18446744073709551615 days",
+        ] {
+            assert_eq!(parse_expires(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn reads_the_header() {
+        let list = "[Adblock Plus 2.0]\n\
+                    ! Title: EasyList\n\
+                    ! Version: 202610190742\n\
+                    ! Expires: 4 days (update frequency)\n\
+                    ! Homepage: https://easylist.to/\n\
+                    ! Diff-Path: ../diff/easylist.patch#easylist\n\
+                    ! Diff-Expires: 1 hours\n\
+                    ||ads.example^\n\
+                    ! Title: Not the header\n";
+        let metadata = ListMetadata::parse(list);
+        assert_eq!(metadata.title.as_deref(), Some("EasyList"));
+        assert_eq!(metadata.version.as_deref(), Some("202610190742"));
+        assert_eq!(metadata.homepage.as_deref(), Some("https://easylist.to/"));
+        assert_eq!(metadata.expires(), Some(Duration::from_secs(4 * DAY)));
+        assert_eq!(
+            metadata.diff_path.as_deref(),
+            Some("../diff/easylist.patch#easylist")
+        );
+        // Lists publishing diffs are checked for them as often as they expire.
+        assert_eq!(
+            metadata.refresh_interval(),
+            Some(Duration::from_secs(60 * 60))
+        );
+    }
+
+    #[test]
+    fn reads_hosts_style_headers() {
+        let list = "# Title: Hosts\n# Expires: 12 hours\n# Expires:\n0.0.0.0 ads.example\n";
+        let metadata = ListMetadata::parse(list);
+        assert_eq!(metadata.title.as_deref(), Some("Hosts"));
+        assert_eq!(
+            metadata.refresh_interval(),
+            Some(Duration::from_secs(12 * 60 * 60))
+        );
+        assert_eq!(
+            ListMetadata::parse("# Expires: soon\n").refresh_interval(),
+            None
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use super::list_store::checksum;
+
+/// Checksums are prefixes of the sha1 of the patched list, this long at the least. Shorter ones
+/// would let corrupted results through.
+const MIN_CHECKSUM_LEN: usize = 10;
+
+/// A differential update of a list, as published at the `Diff-Path` of its header.
+/// The patch file holds one or more blocks like the following, each an RCS style diff (`diff -n`)
+/// of a single list:
+/// ```text
+/// diff name:easylist lines:3 checksum:0123456789
+/// d12 1
+/// a40 1
+/// ||ads.example.com^
+/// ```
+/// Files without `diff` headers are the diff of a single list, without a checksum.
+#[derive(Debug)]
+pub struct ListPatch {
+    commands: Vec<String>,
+    checksum: Option<String>,
+}
+
+impl ListPatch {
+    /// Picks the diff of the list out of the patch file. The name is the anchor of the list's
+    /// `Diff-Path` (i.e. `easylist` of `../patches/easylist.patch#easylist`), without one the
+    /// first diff is used.
+    pub fn parse(patch_file: &str, name: Option<&str>) -> Result<Self, String> {
+        let mut lines = patch_file.lines();
+        if !patch_file.starts_with("diff ") {
+            return Ok(ListPatch {
+                commands: lines.map(str::to_string).collect(),
+                checksum: None,
+            });
+        }
+        while let Some(header) = lines.next() {
+            let Some(fields) = header.strip_prefix("diff ") else {
+                return Err(format!("Expected a diff header, found `{}`", header));
+            };
+            let field = |key: &str| {
+                fields
+                    .split_whitespace()
+                    .find_map(|field| field.strip_prefix(key)?.strip_prefix(':'))
+            };
+            let line_count: usize = field("lines")
+                .and_then(|lines| lines.parse().ok())
+                .ok_or_else(|| format!("Diff header without line count: `{}`", header))?;
+            let commands: Vec<String> = lines
+                .by_ref()
+                .take(line_count)
+                .map(str::to_string)
+                .collect();
+            if commands.len() < line_count {
+                return Err("Diff is cut short".to_string());
+            }
+            if name.is_none_or(|name| field("name") == Some(name)) {
+                return Ok(ListPatch {
+                    commands,
+                    checksum: field("checksum").map(str::to_string),
+                });
+            }
+        }
+        Err(format!("No diff for {} in patch", name.unwrap_or_default()))
+    }
+
+    /// The content of the list with the patch applied. Fails if the patch does not fit the
+    /// content or the result does not match the checksum.
+    pub fn apply(&self, content: &str) -> Result<String, String> {
+        let original: Vec<&str> = content.lines().collect();
+        let mut patched: Vec<&str> = Vec::with_capacity(original.len());
+        let mut commands = self.commands.iter();
+        // Commands refer to lines of the original, in ascending order.
+        let mut copied = 0;
+        while let Some(command) = commands.next() {
+            let invalid = || format!("Invalid diff command `{}`", command);
+            // Both operations are a single ascii character, so slicing past it is safe.
+            let (op, args) = match command.chars().next() {
+                Some(op @ ('a' | 'd')) => (op, &command[1..]),
+                _ => return Err(invalid()),
+            };
+            let (line, count) = args
+                .split_once(' ')
+                .and_then(|(line, count)| {
+                    Some((line.parse::<usize>().ok()?, count.parse::<usize>().ok()?))
+                })
+                .ok_or_else(invalid)?;
+            // Deletions start at the line, additions go after it.
+            let keep_until = match op {
+                'd' => line.checked_sub(1),
+                _ => Some(line),
+            }
+            .filter(|keep_until| *keep_until >= copied && *keep_until <= original.len())
+            .ok_or_else(|| format!("Diff command `{}` does not fit the list", command))?;
+            patched.extend(&original[copied..keep_until]);
+            copied = keep_until;
+            if op == 'd' {
+                copied = copied
+                    .checked_add(count)
+                    .filter(|end| *end <= original.len())
+                    .ok_or_else(|| format!("Diff command `{}` does not fit the list", command))?;
+            } else {
+                for _ in 0..count {
+                    let added = commands
+                        .next()
+                        .ok_or_else(|| format!("Diff command `{}` is cut short", command))?;
+                    patched.push(added.as_str());
+                }
+            }
+        }
+        patched.extend(&original[copied..]);
+
+        let mut patched = patched.join("\n");
+        if content.ends_with('\n') {
+            patched.push('\n');
+        }
+        if let Some(expected) = &self.checksum {
+            if expected.len() < MIN_CHECKSUM_LEN {
+                return Err(format!("Checksum `{}` is too short", expected));
+            }
+            let actual = checksum(&patched);
+            if !actual.starts_with(expected.as_str()) {
+                return Err(format!(
+                    "Checksum mismatch, expected {} but got {}",
+                    expected, actual
+                ));
+            }
+        }
+        Ok(patched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = "! Title: Test\n||a.example^\n||b.example^\n||c.example^\n";
+
+    fn patch(commands: &str) -> ListPatch {
+        ListPatch::parse(commands, None).unwrap()
+    }
+
+    #[test]
+    fn adds_lines_after_the_given_line() {
+        let patched = patch("a2 2\n||x.example^\n||y.example^").apply(LIST);
+        assert_eq!(
+            patched.unwrap(),
+            "! Title: Test\n||a.example^\n||x.example^\n||y.example^\n||b.example^\n||c.example^\n"
+        );
+    }
+
+    #[test]
+    fn deletes_lines_starting_at_the_given_line() {
+        let patched = patch("d2 2").apply(LIST);
+        assert_eq!(patched.unwrap(), "! Title: Test\n||c.example^\n");
+    }
+
+    #[test]
+    fn applies_commands_in_order() {
+        let patched = patch("d1 1\na4 1\n||z.example^").apply(LIST);
+        assert_eq!(
+            patched.unwrap(),
+            "||a.example^\n||b.example^\n||c.example^\n||z.example^\n"
+        );
+    }
+
+    #[test]
+    fn rejects_commands_out_of_range() {
+        assert!(patch("d4 2").apply(LIST).is_err());
+        assert!(patch("d0 1").apply(LIST).is_err());
+        assert!(patch("a5 1\n||x.example^").apply(LIST).is_err());
+        assert!(patch("d3 1\nd1 1").apply(LIST).is_err());
+        assert!(patch("d2 18446744073709551615").apply(LIST).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        assert!(patch("\u{e9}bc 1").apply(LIST).is_err());
+        assert!(patch("c2 1").apply(LIST).is_err());
+        assert!(patch("d2").apply(LIST).is_err());
+        assert!(patch("d2 x").apply(LIST).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_patches() {
+        assert!(patch("a2 2\n||x.example^").apply(LIST).is_err());
+        let patch_file = "diff name:test lines:3 checksum:0123456789\na2 2\n||x.example^";
+        assert!(ListPatch::parse(patch_file, Some("test")).is_err());
+    }
+
+    #[test]
+    fn picks_the_diff_of_the_named_list() {
+        let patch_file = "diff name:other lines:1\nd1 1\ndiff name:test lines:1\nd2 1\n";
+        let patched = ListPatch::parse(patch_file, Some("test"))
+            .unwrap()
+            .apply(LIST);
+        assert_eq!(
+            patched.unwrap(),
+            "! Title: Test\n||b.example^\n||c.example^\n"
+        );
+        assert!(ListPatch::parse(patch_file, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn checks_the_checksum_of_the_result() {
+        let expected = checksum("! Title: Test\n||c.example^\n");
+        let patch_file = format!(
+            "diff name:test lines:1 checksum:{}\nd2 2\n",
+            &expected[..10]
+        );
+        let patch = ListPatch::parse(&patch_file, Some("test")).unwrap();
+        assert!(patch.apply(LIST).is_ok());
+
+        // Longer prefixes of the sha1 will do, shorter ones check next to nothing.
+        let patch_file = format!(
+            "diff name:test lines:1 checksum:{}\nd2 2\n",
+            &expected[..12]
+        );
+        let patch = ListPatch::parse(&patch_file, Some("test")).unwrap();
+        assert!(patch.apply(LIST).is_ok());
+        let patch_file = format!("diff name:test lines:1 checksum:{}\nd2 2\n", &expected[..7]);
+        let patch = ListPatch::parse(&patch_file, Some("test")).unwrap();
+        assert!(patch.apply(LIST).is_err());
+        let patch = ListPatch::parse("diff name:test lines:1 checksum:\nd2 2\n", Some("test"));
+        assert!(patch.unwrap().apply(LIST).is_err());
+
+        let patch_file = "diff name:test lines:1 checksum:0000000000\nd2 2\n";
+        let patch = ListPatch::parse(patch_file, Some("test")).unwrap();
+        assert!(patch.apply(LIST).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+use super::ip_cidr::IpCidr;
+use super::source::ListFormat;
+
+/// Where in a list a rule came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleOrigin {
+    /// One based, as an editor would show it.
+    pub line: usize,
+    /// The line as written in the list.
+    pub rule: String,
+}
+
+/// The parsed content of a single block list.
+/// Domains are stored lower cased and without the trailing dot, each keyed to the rule that
+/// produced it. Entries that are ip addresses or ranges rather than domains are matched against
+/// the addresses in upstream answers.
+#[derive(Clone, Default, Debug)]
+pub struct ListRules {
+    blocked: HashMap<String, RuleOrigin>,
+    exceptions: HashMap<String, RuleOrigin>,
+    blocked_ips: Vec<(IpCidr, RuleOrigin)>,
+    // Adblock rules apply to the domain and all of its subdomains, hosts style lists have to spell
+    // out every host.
+    match_subdomains: bool,
+    // Lines other than comments, and those among them that are not valid in the list's format.
+    // Rules that are valid but mean nothing for DNS (i.e. cosmetic adblock rules) are not
+    // invalid.
+    rule_lines: usize,
+    invalid_lines: usize,
+}
+
+impl ListRules {
+    pub fn parse(content: &str, format: ListFormat) -> Self {
+        let mut rules = ListRules {
+            match_subdomains: format == ListFormat::Adblock,
+            ..Default::default()
+        };
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let origin = RuleOrigin {
+                line: idx + 1,
+                rule: line.to_string(),
+            };
+            match format {
+                ListFormat::Adblock => rules.parse_adblock_line(origin),
+                ListFormat::Hosts => rules.parse_hosts_line(origin),
+                ListFormat::Domains => rules.parse_domains_line(origin),
+            }
+        }
+        rules
+    }
+
+    /// Number of rules (blocking and exceptions) this list contributes.
+    pub fn len(&self) -> usize {
+        self.blocked.len() + self.exceptions.len() + self.blocked_ips.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// What the list holds, comparable across versions of it: its domains, its exceptions
+    /// (prefixed with `@@`) and its address ranges.
+    pub fn entries(&self) -> HashSet<String> {
+        self.blocked
+            .keys()
+            .cloned()
+            .chain(self.exceptions.keys().map(|domain| format!("@@{}", domain)))
+            .chain(self.blocked_ips.iter().map(|(cidr, _)| cidr.to_string()))
+            .collect()
+    }
+
+    /// The share of the lines (comments aside) that are not valid in the list's format, i.e.
+    /// because the list turned out to be an html page.
+    pub fn invalid_ratio(&self) -> f64 {
+        match self.rule_lines {
+            0 => 0.0,
+            rule_lines => self.invalid_lines as f64 / rule_lines as f64,
+        }
+    }
+
+    /// The rule blocking the domain, if any.
+    pub fn blocks(&self, domain: &str) -> Option<&RuleOrigin> {
+        self.candidates(domain)
+            .find_map(|candidate| self.blocked.get(candidate))
+    }
+
+    /// The exception rule allowing the domain, if any.
+    pub fn excepts(&self, domain: &str) -> Option<&RuleOrigin> {
+        self.candidates(domain)
+            .find_map(|candidate| self.exceptions.get(candidate))
+    }
+
+    /// The rule blocking the address, if any.
+    pub fn blocks_ip(&self, ip: &IpAddr) -> Option<&RuleOrigin> {
+        self.blocked_ips
+            .iter()
+            .find(|(cidr, _)| cidr.contains(ip))
+            .map(|(_, origin)| origin)
+    }
+
+    /// The domain itself followed by its parents if this list's rules cover subdomains.
+    fn candidates<'a>(&self, domain: &'a str) -> impl Iterator<Item = &'a str> {
+        let match_subdomains = self.match_subdomains;
+        std::iter::successors(Some(domain), move |domain| {
+            if !match_subdomains {
+                return None;
+            }
+            domain.split_once('.').map(|(_, parent)| parent)
+        })
+    }
+
+    /// Only network rules anchored to a domain with no further restriction are taken, i.e.
+    /// `||example.com^` and `@@||example.com^`. The `$important` and `$all` options are tolerated
+    /// since they do not narrow down what is blocked.
+    /// Lines are only invalid if they cannot be adblock rules at all (i.e. html), since browser
+    /// only rules look like anything.
+    fn parse_adblock_line(&mut self, origin: RuleOrigin) {
+        let line = origin.rule.as_str();
+        if line.starts_with('!') || line.starts_with('[') {
+            return;
+        }
+        self.rule_lines += 1;
+        if line.starts_with('<') {
+            self.invalid_lines += 1;
+            return;
+        }
+        let (line, is_exception) = match line.strip_prefix("@@") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let Some(rule) = line.strip_prefix("||") else {
+            return;
+        };
+        let (rule, options) = match rule.split_once('$') {
+            Some((rule, options)) => (rule, Some(options)),
+            None => (rule, None),
+        };
+        if let Some(options) = options {
+            if !options
+                .split(',')
+                .all(|option| option == "important" || option == "all")
+            {
+                return;
+            }
+        }
+        let Some(domain) = rule.strip_suffix('^') else {
+            return;
+        };
+        if let Ok(cidr) = domain.parse::<IpCidr>() {
+            if !is_exception {
+                self.blocked_ips.push((cidr, origin));
+            }
+            return;
+        }
+        // Rules with a path or a wildcard are fine for a browser, there is just nothing to take
+        // from them for DNS.
+        match normalize_domain(domain) {
+            Some(domain) if is_exception => _ = self.exceptions.insert(domain, origin),
+            Some(domain) => _ = self.blocked.insert(domain, origin),
+            None => {}
+        }
+    }
+
+    fn parse_hosts_line(&mut self, origin: RuleOrigin) {
+        let line = match origin.rule.split_once('#') {
+            Some((line, _comment)) => line,
+            None => origin.rule.as_str(),
+        };
+        let mut parts = line.split_whitespace();
+        let Some(ip) = parts.next() else {
+            return;
+        };
+        self.rule_lines += 1;
+        if ip.parse::<IpAddr>().is_err() {
+            self.invalid_lines += 1;
+            return;
+        }
+        for host in parts {
+            if matches!(
+                host,
+                "localhost" | "localhost.localdomain" | "broadcasthost" | "local"
+            ) {
+                continue;
+            }
+            match normalize_domain(host) {
+                Some(domain) => _ = self.blocked.insert(domain, origin.clone()),
+                None => {
+                    self.invalid_lines += 1;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn parse_domains_line(&mut self, origin: RuleOrigin) {
+        let line = origin.rule.as_str();
+        if line.starts_with('#') || line.starts_with('!') {
+            return;
+        }
+        self.rule_lines += 1;
+        if let Ok(cidr) = line.parse::<IpCidr>() {
+            self.blocked_ips.push((cidr, origin));
+            return;
+        }
+        match normalize_domain(line) {
+            Some(domain) => _ = self.blocked.insert(domain, origin),
+            None => self.invalid_lines += 1,
+        }
+    }
+}
+
+/// Lower cases the domain and strips the trailing dot.
+/// Returns `None` for anything that cannot possibly be a domain name (wildcards, paths etc).
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_end_matches('.');
+    if domain.is_empty()
+        || !domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+    {
+        return None;
+    }
+    Some(domain.to_ascii_lowercase())
+}
+
+/// Whether `domain` is `parent` or one of its subdomains. Both are expected to be normalized.
+pub fn is_same_or_subdomain(domain: &str, parent: &str) -> bool {
+    domain == parent
+        || domain
+            .strip_suffix(parent)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adblock_rules_meaning_nothing_for_dns_are_not_invalid() {
+        let list = "! Title: Test\n\
+                    ||ads.example^\n\
+                    ||example.com/path^\n\
+                    ||*.example.com^\n\
+                    ##.banner\n\
+                    ||tracker.example^$third-party\n";
+        let rules = ListRules::parse(list, ListFormat::Adblock);
+
+        assert_eq!(rules.len(), 1);
+        assert!(rules.blocks("sub.ads.example").is_some());
+        assert_eq!(rules.invalid_ratio(), 0.0);
+    }
+
+    #[test]
+    fn html_is_invalid() {
+        let page = "<!DOCTYPE html>\n<html>\n<body>\n||ads.example^\n</body>\n</html>\n";
+        let rules = ListRules::parse(page, ListFormat::Adblock);
+
+        assert_eq!(rules.invalid_ratio(), 5.0 / 6.0);
+    }
+}
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use super::list_diff::ListDiff;
+
+/// Snapshots are named `block_list_<source>_<timestamp>_<sha1>.txt`. Those stored before
+/// checksums were introduced lack the `_<sha1>` part and are read unchecked.
+const TIMESTAMP_FORMAT: &str = "%y-%m-%d-%H:%M:%S";
+
+/// Before lists had names the only list, EasyList, was stored as `block_list_<timestamp>.txt`.
+/// Those files are taken as snapshots of the default list of that name.
+const LEGACY_LIST_NAME: &str = "easylist";
+
+/// How many snapshots of each list are kept unless told otherwise.
+const DEFAULT_RETENTION: usize = 5;
+
+/// A copy of a list as it was downloaded (or patched, or read from disk) at some point.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The timestamp part of the file name, which is how rollbacks refer to the snapshot.
+    pub id: String,
+    pub path: PathBuf,
+    pub taken_at: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl Snapshot {
+    /// Makes sense of the file name of a snapshot of the named list.
+    pub fn from_path(path: &Path, name: &str) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let rest = file_name
+            .strip_prefix(&format!("block_list_{}_", name))
+            .or_else(|| {
+                file_name
+                    .strip_prefix("block_list_")
+                    .filter(|_| name == LEGACY_LIST_NAME)
+            })?
+            .strip_suffix(".txt")?;
+        let (id, checksum) = match rest.split_once('_') {
+            Some((id, checksum)) => (id, Some(checksum.to_string())),
+            None => (rest, None),
+        };
+        // Also tells apart lists whose names share a prefix, i.e. `ads` and `ads_extra`.
+        let taken_at = NaiveDateTime::parse_from_str(id, TIMESTAMP_FORMAT)
+            .ok()
+            .and_then(|taken_at| Local.from_local_datetime(&taken_at).earliest())?;
+        Some(Snapshot {
+            id: id.to_string(),
+            path: path.to_path_buf(),
+            taken_at,
+            checksum,
+        })
+    }
+
+    /// The content of the snapshot, provided it is still what was stored.
+    pub async fn read(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let content = String::from_utf8(tokio::fs::read(&self.path).await?)?;
+        if let Some(expected) = &self.checksum {
+            let actual = checksum(&content);
+            if actual != *expected {
+                return Err(format!(
+                    "Checksum mismatch, expected {} but got {}",
+                    expected, actual
+                )
+                .into());
+            }
+        }
+        Ok(content)
+    }
+}
+
+/// Where copies of the lists are kept: the db directory. Only the newest few snapshots of
+/// each list are kept around.
+#[derive(Clone, Debug)]
+pub struct ListStore {
+    db_dir: PathBuf,
+    retention: usize,
+}
+
+impl ListStore {
+    pub fn new(db_dir: PathBuf) -> Self {
+        ListStore {
+            db_dir,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Keeps this many snapshots of each list, at least one.
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention.max(1);
+        self
+    }
+
+    /// Writes the content of the list into the db directory, named after the time and its
+    /// checksum. The file only shows up under its name once it is completely written, so a crash
+    /// halfway leaves no truncated snapshot behind. Older snapshots beyond the retention are
+    /// removed afterwards.
+    pub async fn store(
+        &self,
+        name: &str,
+        taken_at: DateTime<Local>,
+        content: &str,
+    ) -> Result<Snapshot, Box<dyn std::error::Error + Send + Sync>> {
+        let id = taken_at.format(TIMESTAMP_FORMAT).to_string();
+        let checksum = checksum(content);
+        let file_name = format!("block_list_{}_{}_{}.txt", name, id, checksum);
+        let path = self.db_dir.join(&file_name);
+        let tmp_path = self.db_dir.join(format!(".{}.tmp", file_name));
+        {
+            let mut file = tokio::fs::File::create(&tmp_path).await?;
+            file.write_all(content.as_bytes()).await?;
+            file.sync_all().await?;
+        }
+        tokio::fs::rename(&tmp_path, &path).await?;
+        // TODO: log this
+        println!("Block list file {} updated", path.display());
+
+        if let Err(e) = self.prune(name).await {
+            // TODO: log this
+            println!(
+                "Old snapshots of block list {} could not be removed: {}",
+                name, e
+            );
+        }
+        Ok(Snapshot {
+            id,
+            path,
+            taken_at,
+            checksum: Some(checksum),
+        })
+    }
+
+    /// The snapshots of the named list, newest first.
+    pub async fn snapshots(
+        &self,
+        name: &str,
+    ) -> Result<Vec<Snapshot>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut snapshots = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.db_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(snapshot) = Snapshot::from_path(&entry.path(), name) {
+                snapshots.push(snapshot);
+            }
+        }
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.taken_at));
+        Ok(snapshots)
+    }
+
+    /// The newest snapshot of the named list, provided it holds the given content.
+    pub async fn newest_with(
+        &self,
+        name: &str,
+        content: &str,
+    ) -> Result<Option<Snapshot>, Box<dyn std::error::Error + Send + Sync>> {
+        let newest = self.snapshots(name).await?.into_iter().next();
+        Ok(newest.filter(|snapshot| snapshot.checksum.as_deref() == Some(&checksum(content))))
+    }
+
+    /// Keeps the diff of the update that produced the snapshot next to it.
+    pub async fn store_diff(
+        &self,
+        snapshot: &Snapshot,
+        diff: &ListDiff,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.diff_path(&diff.list, &snapshot.id);
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec(diff)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+
+    /// The diff of the update that produced the snapshot with the given id. Snapshots of lists
+    /// that had not been loaded before have none.
+    pub async fn diff(
+        &self,
+        name: &str,
+        id: &str,
+    ) -> Result<Option<ListDiff>, Box<dyn std::error::Error + Send + Sync>> {
+        let content = match tokio::fs::read(self.diff_path(name, id)).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    fn diff_path(&self, name: &str, id: &str) -> PathBuf {
+        self.db_dir
+            .join(format!("block_list_{}_{}.diff.json", name, id))
+    }
+
+    /// Removes all but the newest snapshots of the named list, along with their diffs.
+    async fn prune(&self, name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for snapshot in self.snapshots(name).await?.iter().skip(self.retention) {
+            tokio::fs::remove_file(&snapshot.path).await?;
+            match tokio::fs::remove_file(self.diff_path(name, &snapshot.id)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            // TODO: log this
+            println!("Removed snapshot {}", snapshot.path.display());
+        }
+        Ok(())
+    }
+}
+
+/// The sha1 of the content in hex.
+pub(super) fn checksum(content: &str) -> String {
+    Sha1::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Timelike};
+
+    /// A db directory of its own for the test.
+    fn db_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustle-{}-{}", std::process::id(), test));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Whole seconds, as in file names.
+    fn taken_at(hours_ago: i64) -> DateTime<Local> {
+        (Local::now() - Duration::hours(hours_ago))
+            .with_nanosecond(0)
+            .unwrap()
+    }
+
+    #[test]
+    fn takes_legacy_files_as_easylist() {
+        let path = Path::new("var/db/block_list_26-10-19-07:45:00.txt");
+        let snapshot = Snapshot::from_path(path, LEGACY_LIST_NAME).unwrap();
+        assert_eq!(snapshot.id, "26-10-19-07:45:00");
+        assert_eq!(snapshot.checksum, None);
+        assert!(Snapshot::from_path(path, "other").is_none());
+    }
+
+    #[tokio::test]
+    async fn rotates_legacy_files_with_new_ones() {
+        let dir = db_dir("legacy");
+        let store = ListStore::new(dir.clone()).with_retention(2);
+        let legacy_id = taken_at(48).format(TIMESTAMP_FORMAT).to_string();
+        let legacy_path = dir.join(format!("block_list_{}.txt", legacy_id));
+        std::fs::write(&legacy_path, "||legacy.example^\n").unwrap();
+
+        let snapshots = store.snapshots(LEGACY_LIST_NAME).await.unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].id, legacy_id);
+        assert_eq!(snapshots[0].read().await.unwrap(), "||legacy.example^\n");
+        assert!(store.snapshots("other").await.unwrap().is_empty());
+
+        // The legacy file is the oldest snapshot, and is kept as long as it is among the newest.
+        let newer = store
+            .store(LEGACY_LIST_NAME, taken_at(24), "||newer.example^\n")
+            .await
+            .unwrap();
+        let ids: Vec<_> = store
+            .snapshots(LEGACY_LIST_NAME)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.id)
+            .collect();
+        assert_eq!(ids, [newer.id.clone(), legacy_id]);
+
+        let newest = store
+            .store(LEGACY_LIST_NAME, taken_at(0), "||newest.example^\n")
+            .await
+            .unwrap();
+        let ids: Vec<_> = store
+            .snapshots(LEGACY_LIST_NAME)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.id)
+            .collect();
+        assert_eq!(ids, [newest.id, newer.id]);
+        assert!(!legacy_path.exists());
+
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn refuses_corrupted_snapshots() {
+        let dir = db_dir("corrupted");
+        let store = ListStore::new(dir.clone());
+        let snapshot = store
+            .store("test", taken_at(0), "||ads.example^\n")
+            .await
+            .unwrap();
+        assert_eq!(snapshot.read().await.unwrap(), "||ads.example^\n");
+        std::fs::write(&snapshot.path, "||other.example^\n").unwrap();
+        assert!(snapshot.read().await.is_err());
+
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}
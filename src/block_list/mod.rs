@@ -0,0 +1,21 @@
+mod ip_cidr;
+mod list_diff;
+mod list_files;
+mod list_metadata;
+mod list_patch;
+mod list_rules;
+mod list_store;
+mod rule_set;
+mod source;
+mod validation;
+
+pub use ip_cidr::*;
+pub use list_diff::*;
+pub use list_files::*;
+pub use list_metadata::*;
+pub use list_patch::*;
+pub use list_rules::*;
+pub use list_store::*;
+pub use rule_set::*;
+pub use source::*;
+pub use validation::*;
@@ -0,0 +1,365 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use super::list_rules::{ListRules, RuleOrigin};
+use super::source::{BlockListSource, SourceStatus, Validators};
+
+/// How long to wait before retrying a list that failed to update, doubled on every further
+/// failure.
+const RETRY_BACKOFF: Duration = Duration::from_secs(60);
+/// Caps the backoff at a bit over 4 hours (unless the regular interval is shorter anyway).
+const MAX_BACKOFF_DOUBLINGS: u32 = 8;
+
+/// A configured source together with what it currently contributes to the rule set.
+#[derive(Clone, Debug, Default)]
+pub struct BlockList {
+    pub source: BlockListSource,
+    pub status: SourceStatus,
+    pub rules: ListRules,
+    /// Set when a refresh was asked for ahead of schedule.
+    pub refresh_requested: bool,
+}
+
+impl BlockList {
+    /// When this list is due for a refresh. Lists that have never been attempted, or that a
+    /// refresh was requested for, are due now. Failing lists are retried with an exponential
+    /// backoff, though never later than their regular interval.
+    pub fn next_refresh(&self) -> DateTime<Local> {
+        if self.refresh_requested {
+            return Local::now();
+        }
+        match self.status.last_attempt {
+            Some(last_attempt) => {
+                let mut interval = self.source.refresh_interval(&self.status.metadata);
+                if self.status.failures > 0 {
+                    let backoff = RETRY_BACKOFF
+                        .saturating_mul(1 << (self.status.failures - 1).min(MAX_BACKOFF_DOUBLINGS));
+                    interval = interval.min(backoff);
+                }
+                // Intervals too large to represent are as good as a year.
+                last_attempt
+                    + chrono::Duration::from_std(interval)
+                        .unwrap_or_else(|_| chrono::Duration::days(365))
+            }
+            None => Local::now(),
+        }
+    }
+
+    /// Whether a list kept up to date with diffs is due for a full download, which keeps diffs
+    /// that went wrong from sticking around forever.
+    pub fn download_due(&self, now: DateTime<Local>) -> bool {
+        self.status.last_downloaded.is_none_or(|last_downloaded| {
+            chrono::Duration::from_std(self.source.download_interval(&self.status.metadata))
+                .is_ok_and(|interval| last_downloaded + interval <= now)
+        })
+    }
+
+    /// Whether the list is to be refreshed at the given time.
+    pub fn is_due(&self, now: DateTime<Local>) -> bool {
+        self.refresh_requested || self.status.last_attempt.is_none() || self.next_refresh() <= now
+    }
+}
+
+/// The rule behind a block decision (or behind an exception to one).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockReason {
+    pub list: String,
+    pub category: String,
+    pub line: usize,
+    pub rule: String,
+    /// Set when the rule did not match the queried domain itself but a name the upstream
+    /// answer aliased it to (i.e. a tracker cloaked behind a first party CNAME).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloaked_target: Option<String>,
+    /// Set when the rule is an address range matching an address in the upstream answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_ip: Option<IpAddr>,
+}
+
+impl BlockReason {
+    /// A reason for blocks decided by configured policies rather than block lists. `line` is
+    /// the (one based) position of the rule within its section of the config.
+    pub fn policy(section: &str, line: usize, rule: String) -> Self {
+        BlockReason {
+            list: section.to_string(),
+            category: "policy".to_string(),
+            line,
+            rule,
+            cloaked_target: None,
+            resolved_ip: None,
+        }
+    }
+
+    fn new(list: &BlockList, origin: &RuleOrigin) -> Self {
+        BlockReason {
+            list: list.source.name.clone(),
+            category: list.source.category.clone(),
+            line: origin.line,
+            rule: origin.rule.clone(),
+            cloaked_target: None,
+            resolved_ip: None,
+        }
+    }
+}
+
+impl std::fmt::Display for BlockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{} `{}`", self.list, self.line, self.rule)?;
+        if let Some(cloaked_target) = &self.cloaked_target {
+            write!(f, " via CNAME {}", cloaked_target)?;
+        }
+        if let Some(resolved_ip) = &self.resolved_ip {
+            write!(f, " resolving to {}", resolved_ip)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything the rule set has to say about a domain.
+/// A domain is blocked only if a blocking rule matches and no exception does.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Explanation {
+    pub domain: String,
+    /// The client group the domain was looked at for, if any.
+    #[serde(default)]
+    pub group: Option<String>,
+    pub blocked: bool,
+    pub blocked_by: Option<BlockReason>,
+    pub allowed_by: Option<BlockReason>,
+    /// Set while blocking is paused. Nothing is blocked in the meantime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_until: Option<DateTime<Local>>,
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.blocked_by, &self.allowed_by) {
+            (Some(blocked_by), None) if self.blocked => {
+                write!(f, "{} is blocked by {}", self.domain, blocked_by)?
+            }
+            (Some(blocked_by), None) => {
+                write!(f, "{} would be blocked by {}", self.domain, blocked_by)?
+            }
+            (Some(blocked_by), Some(allowed_by)) => write!(
+                f,
+                "{} would be blocked by {} but is allowed by {}",
+                self.domain, blocked_by, allowed_by
+            )?,
+            (None, _) => write!(f, "{} is not blocked", self.domain)?,
+        }
+        if let Some(group) = &self.group {
+            write!(f, " (group {})", group)?;
+        }
+        if let Some(paused_until) = &self.paused_until {
+            write!(
+                f,
+                ", but blocking is paused until {}",
+                paused_until.format("%H:%M:%S")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What gets reported about a block list, minus the rules themselves.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockListReport {
+    pub source: BlockListSource,
+    pub status: SourceStatus,
+    /// When the list is refreshed next, disabled lists are not.
+    pub next_refresh: Option<DateTime<Local>>,
+}
+
+impl std::fmt::Display for BlockListReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source.name)?;
+        let metadata = &self.status.metadata;
+        match (&metadata.title, &metadata.version) {
+            (Some(title), Some(version)) => write!(f, " ({} {})", title, version)?,
+            (Some(title), None) => write!(f, " ({})", title)?,
+            (None, Some(version)) => write!(f, " (version {})", version)?,
+            (None, None) => {}
+        }
+        write!(f, ": {} entries", self.status.entry_count)?;
+        if let Some(last_updated) = &self.status.last_updated {
+            write!(f, ", updated {}", last_updated.format("%Y-%m-%d %H:%M"))?;
+        }
+        match &self.next_refresh {
+            Some(next_refresh) => write!(
+                f,
+                ", next refresh {}",
+                next_refresh.format("%Y-%m-%d %H:%M")
+            )?,
+            None => write!(f, ", disabled")?,
+        }
+        if let Some(last_change) = &self.status.last_change {
+            write!(
+                f,
+                " ({} added, {} removed)",
+                last_change.added, last_change.removed
+            )?;
+        }
+        if let Some(last_error) = &self.status.last_error {
+            write!(
+                f,
+                ", failing ({} in a row): {}",
+                self.status.failures, last_error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The active rule set, i.e. every configured block list merged.
+/// Disabled lists are kept around (so they can be turned back on without a refetch) but are not
+/// consulted.
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    lists: Vec<BlockList>,
+}
+
+impl RuleSet {
+    pub fn new(sources: Vec<BlockListSource>) -> Self {
+        RuleSet {
+            lists: sources
+                .into_iter()
+                .map(|source| BlockList {
+                    source,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the rule of the first selected list whose address ranges cover the address.
+    pub fn find_ip(&self, ip: &IpAddr, selection: Option<&[String]>) -> Option<BlockReason> {
+        self.selected(selection).find_map(|list| {
+            list.rules.blocks_ip(ip).map(|origin| BlockReason {
+                resolved_ip: Some(*ip),
+                ..BlockReason::new(list, origin)
+            })
+        })
+    }
+
+    /// Looks the domain up in the selected lists.
+    /// An exception in any selected list takes precedence over every block.
+    pub fn explain(&self, domain: &str, selection: Option<&[String]>) -> Explanation {
+        let blocked_by = self.selected(selection).find_map(|list| {
+            list.rules
+                .blocks(domain)
+                .map(|origin| BlockReason::new(list, origin))
+        });
+        let allowed_by = self.selected(selection).find_map(|list| {
+            list.rules
+                .excepts(domain)
+                .map(|origin| BlockReason::new(list, origin))
+        });
+        Explanation {
+            domain: domain.to_string(),
+            group: None,
+            blocked: blocked_by.is_some() && allowed_by.is_none(),
+            blocked_by,
+            allowed_by,
+            paused_until: None,
+        }
+    }
+
+    /// The enabled lists whose schedule is active, narrowed down to the given names if there is
+    /// a selection.
+    fn selected<'a>(
+        &'a self,
+        selection: Option<&'a [String]>,
+    ) -> impl Iterator<Item = &'a BlockList> + 'a {
+        let now = Utc::now();
+        self.lists.iter().filter(move |list| {
+            list.source.enabled
+                && list.source.schedule.is_active(now)
+                && selection.is_none_or(|selection| selection.contains(&list.source.name))
+        })
+    }
+
+    /// Enabled sources whose refresh is due at the given time.
+    pub fn due_sources(&self, now: DateTime<Local>) -> Vec<BlockListSource> {
+        self.lists
+            .iter()
+            .filter(|list| list.source.enabled && list.is_due(now))
+            .map(|list| list.source.clone())
+            .collect()
+    }
+
+    /// The earliest refresh among the enabled lists.
+    pub fn next_refresh(&self) -> Option<DateTime<Local>> {
+        self.lists
+            .iter()
+            .filter(|list| list.source.enabled)
+            .map(BlockList::next_refresh)
+            .min()
+    }
+
+    pub fn lists(&self) -> &[BlockList] {
+        &self.lists
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BlockList> {
+        self.lists.iter().find(|list| list.source.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut BlockList> {
+        self.lists.iter_mut().find(|list| list.source.name == name)
+    }
+
+    /// Adds a source, replacing the configuration of the one with the same name if present.
+    /// Replacing keeps the rules loaded so far until the next refresh of the source, which is
+    /// right away if the location changed.
+    pub fn upsert_source(&mut self, source: BlockListSource) {
+        match self.get_mut(&source.name) {
+            Some(list) => {
+                // Validators of another location say nothing about the new one, and the rules
+                // of the old one should not serve until it is due.
+                if list.source.location != source.location {
+                    list.status.validators = Validators::default();
+                    list.refresh_requested = true;
+                }
+                list.source = source;
+            }
+            None => self.lists.push(BlockList {
+                source,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Has the named list, or every list, refreshed on the next wake up of the update task.
+    /// Returns false if there is no list with the given name.
+    pub fn request_refresh(&mut self, name: Option<&str>) -> bool {
+        let mut found = false;
+        for list in &mut self.lists {
+            if name.is_none_or(|name| name == list.source.name) {
+                list.refresh_requested = true;
+                found = true;
+            }
+        }
+        found || name.is_none()
+    }
+
+    pub fn remove_source(&mut self, name: &str) -> Option<BlockList> {
+        let idx = self
+            .lists
+            .iter()
+            .position(|list| list.source.name == name)?;
+        Some(self.lists.remove(idx))
+    }
+
+    pub fn report(&self) -> Vec<BlockListReport> {
+        self.lists
+            .iter()
+            .map(|list| BlockListReport {
+                source: list.source.clone(),
+                status: list.status.clone(),
+                next_refresh: list.source.enabled.then(|| list.next_refresh()),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,273 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+
+use super::list_diff::DiffSummary;
+use super::list_metadata::ListMetadata;
+use super::validation::Validation;
+use crate::policy::Schedule;
+
+/// Refresh once every week unless told otherwise.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+
+/// Sources configured to be refreshed more often than this are refreshed every minute.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Shared by every download so connections to the same host get reused.
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// Where the content of a block list comes from.
+/// Anything that looks like a http(s) url is fetched remotely, everything else is treated as a
+/// path on the local file system.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum SourceLocation {
+    Url(String),
+    Path(PathBuf),
+}
+
+impl From<String> for SourceLocation {
+    fn from(s: String) -> Self {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            SourceLocation::Url(s)
+        } else {
+            SourceLocation::Path(PathBuf::from(s))
+        }
+    }
+}
+
+impl From<SourceLocation> for String {
+    fn from(location: SourceLocation) -> Self {
+        match location {
+            SourceLocation::Url(url) => url,
+            SourceLocation::Path(path) => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// The syntax a block list is written in.
+/// - `Adblock`: EasyList style rules. Only rules anchored to a domain (i.e. `||example.com^`) are
+///   meaningful for DNS, everything else (cosmetic rules, url patterns) is skipped.
+/// - `Hosts`: hosts file style lines (i.e. `0.0.0.0 example.com`).
+/// - `Domains`: one domain per line.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListFormat {
+    #[default]
+    Adblock,
+    Hosts,
+    Domains,
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adblock" => Ok(ListFormat::Adblock),
+            "hosts" => Ok(ListFormat::Hosts),
+            "domains" => Ok(ListFormat::Domains),
+            _ => Err(format!("Unknown list format {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ListFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListFormat::Adblock => write!(f, "adblock"),
+            ListFormat::Hosts => write!(f, "hosts"),
+            ListFormat::Domains => write!(f, "domains"),
+        }
+    }
+}
+
+/// Configuration of a single block list.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockListSource {
+    pub name: String,
+    pub location: SourceLocation,
+    #[serde(default)]
+    pub format: ListFormat,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub category: String,
+    /// Takes precedence over the `Expires` the list declares.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_interval_secs: Option<u64>,
+    /// When the list blocks, always if left out. It is kept up to date either way.
+    #[serde(default, skip_serializing_if = "Schedule::is_always")]
+    pub schedule: Schedule,
+    /// What a download of a remote list has to pass to be put in use.
+    #[serde(default, skip_serializing_if = "Validation::is_default")]
+    pub validation: Validation,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for BlockListSource {
+    /// The list rustle ships with when nothing else is configured.
+    fn default() -> Self {
+        BlockListSource {
+            name: "easylist".to_string(),
+            location: SourceLocation::Url("https://easylist.to/easylist/easylist.txt".to_string()),
+            format: ListFormat::Adblock,
+            enabled: true,
+            category: "ads".to_string(),
+            refresh_interval_secs: None,
+            schedule: Schedule::default(),
+            validation: Validation::default(),
+        }
+    }
+}
+
+impl BlockListSource {
+    /// How often the list is checked for updates: the configured interval, else what the list
+    /// asks for in its header, else a week.
+    pub fn refresh_interval(&self, metadata: &ListMetadata) -> Duration {
+        self.refresh_interval_secs
+            .map(|secs| Duration::from_secs(secs).max(MIN_REFRESH_INTERVAL))
+            .or(metadata.refresh_interval())
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL)
+    }
+
+    /// How often a list that is kept up to date with diffs is downloaded in full anyway.
+    pub fn download_interval(&self, metadata: &ListMetadata) -> Duration {
+        metadata
+            .expires()
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL)
+            .max(self.refresh_interval(metadata))
+    }
+
+    /// Reads a json array of sources from the given path.
+    pub async fn load_all(
+        path: &Path,
+    ) -> Result<Vec<Self>, Box<dyn std::error::Error + Send + Sync>> {
+        let content = tokio::fs::read(path).await?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// Downloads the differential update at the given path, relative to the url of the list.
+    /// `None` if it has not been published yet, which means the list has not changed since.
+    pub async fn fetch_patch(
+        &self,
+        diff_path: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let SourceLocation::Url(url) = &self.location else {
+            return Err("Only remote lists can be patched".into());
+        };
+        let url = reqwest::Url::parse(url)?.join(diff_path)?;
+        let response = HTTP_CLIENT.get(url).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.text().await?))
+    }
+
+    /// Retrieves the raw content of the list, be it from the network or from disk.
+    /// Remote lists are only downloaded if they changed since the download the validators came
+    /// from, compressed if the server supports it.
+    pub async fn fetch(
+        &self,
+        validators: &Validators,
+    ) -> Result<Fetched, Box<dyn std::error::Error + Send + Sync>> {
+        match &self.location {
+            SourceLocation::Url(url) => {
+                let mut request = HTTP_CLIENT.get(url);
+                if let Some(etag) = &validators.etag {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &validators.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified);
+                }
+                let response = request.send().await?.error_for_status()?;
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(Fetched::NotModified);
+                }
+                let header = |name| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                let validators = Validators {
+                    etag: header(ETAG),
+                    last_modified: header(LAST_MODIFIED),
+                };
+                let content_type = header(CONTENT_TYPE);
+                Ok(Fetched::Modified {
+                    content: response.text().await?,
+                    content_type,
+                    validators,
+                })
+            }
+            SourceLocation::Path(path) => {
+                let content = tokio::fs::read(path).await?;
+                Ok(Fetched::Modified {
+                    content: String::from_utf8(content)?,
+                    content_type: None,
+                    validators: Validators::default(),
+                })
+            }
+        }
+    }
+}
+
+/// What a fetch came back with.
+pub enum Fetched {
+    Modified {
+        content: String,
+        content_type: Option<String>,
+        validators: Validators,
+    },
+    /// The stored list with a differential update applied.
+    Patched(String),
+    /// The list is the same as the last time it was downloaded.
+    NotModified,
+}
+
+/// The `ETag` and `Last-Modified` headers of the last download of a remote list, handed back to
+/// the server so it can tell us the list has not changed.
+#[derive(Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// Book keeping of how a given source has been doing.
+/// `last_attempt` is bumped on failures too, so a broken source is retried with a backoff rather
+/// than on every wake up of the update task. The rules of the last successful update stay in use
+/// in the meantime.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct SourceStatus {
+    pub last_updated: Option<DateTime<Local>>,
+    pub last_attempt: Option<DateTime<Local>>,
+    pub entry_count: usize,
+    pub last_error: Option<String>,
+    /// Failed attempts since the last successful update.
+    pub failures: u32,
+    /// When the list was last downloaded in full, as opposed to patched.
+    pub last_downloaded: Option<DateTime<Local>>,
+    /// Where the list as it is in use was last stored, which is what diffs are applied to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<PathBuf>,
+    #[serde(flatten)]
+    pub validators: Validators,
+    /// What the list declared about itself in its header the last time it was loaded.
+    pub metadata: ListMetadata,
+    /// How much the last update changed the list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_change: Option<DiffSummary>,
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use super::list_rules::ListRules;
+
+/// Checks a downloaded list has to pass before it replaces the one in use, so that a source
+/// serving an error page or a truncated file does not wipe out blocking.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Validation {
+    /// Lists with fewer rules are rejected.
+    pub min_rules: usize,
+    /// Lists losing a larger share of the rules of the list in use are rejected.
+    pub max_shrink_percent: f64,
+    /// Lists with a larger share of lines that are not valid in their format are rejected.
+    pub max_invalid_percent: f64,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Validation {
+            min_rules: 1,
+            max_shrink_percent: 50.0,
+            max_invalid_percent: 10.0,
+        }
+    }
+}
+
+impl Validation {
+    pub fn is_default(&self) -> bool {
+        *self == Validation::default()
+    }
+
+    /// Returns why the list is not fit to replace one with `previous_len` rules, if it is not.
+    pub fn check(
+        &self,
+        content: &str,
+        content_type: Option<&str>,
+        rules: &ListRules,
+        previous_len: usize,
+    ) -> Result<(), String> {
+        if content_type.is_some_and(|content_type| content_type.starts_with("text/html"))
+            || looks_like_html(content)
+        {
+            return Err("Got an html page rather than a list".to_string());
+        }
+        if rules.len() < self.min_rules {
+            return Err(format!(
+                "Only {} rules, expected at least {}",
+                rules.len(),
+                self.min_rules
+            ));
+        }
+        if previous_len > 0 && rules.len() < previous_len {
+            let shrink_percent = (previous_len - rules.len()) as f64 * 100.0 / previous_len as f64;
+            if shrink_percent > self.max_shrink_percent {
+                return Err(format!(
+                    "Shrank by {:.0}% from {} to {} rules, allowed are {}%",
+                    shrink_percent,
+                    previous_len,
+                    rules.len(),
+                    self.max_shrink_percent
+                ));
+            }
+        }
+        let invalid_percent = rules.invalid_ratio() * 100.0;
+        if invalid_percent > self.max_invalid_percent {
+            return Err(format!(
+                "{:.0}% of the lines are invalid, allowed are {}%",
+                invalid_percent, self.max_invalid_percent
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn looks_like_html(content: &str) -> bool {
+    let start: String = content.trim_start().chars().take(16).collect();
+    let start = start.to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
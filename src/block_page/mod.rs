@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::http::{Request, Response};
+use crate::{LongRunningTaskType, OpaqueError, QueryService, Ready};
+
+mod recent_blocks;
+mod routes;
+mod unblock_request;
+
+pub use recent_blocks::*;
+pub use unblock_request::*;
+
+/// Provider for the task serving the block page.
+/// With an ip blocking mode, blocked domains resolve to an address this server listens on, so
+/// users opening them in a browser learn what was blocked and why instead of facing a connection
+/// error. Only plain http can be served, browsers insisting on https still get an error.
+pub async fn serve(
+    addr: &str,
+    query_service: Arc<QueryService<Ready>>,
+) -> Result<LongRunningTaskType, OpaqueError> {
+    let listener = TcpListener::bind(addr).await?;
+    // TODO: log this
+    println!("Block page listening on {}", listener.local_addr()?);
+
+    let block_page_task = tokio::spawn(async move {
+        loop {
+            let (mut stream, client_addr) = listener.accept().await?;
+            let query_service = query_service.clone();
+
+            tokio::spawn(async move {
+                let response = match Request::read_from(&mut stream).await {
+                    Ok(request) => {
+                        routes::handle(request, client_addr.ip().to_canonical(), &query_service)
+                            .await
+                    }
+                    Err(e) => Response::text(400, &e.to_string()),
+                };
+                response.write_to(&mut stream).await?;
+
+                Ok::<(), OpaqueError>(())
+            });
+        }
+        // TODO: Add shutdown routine
+        #[allow(unreachable_code)]
+        Ok::<(), OpaqueError>(())
+    });
+
+    Ok(block_page_task)
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::block_list::BlockReason;
+
+/// How long the reason of a block is remembered. Browsers open the block page right after the
+/// blocked answer, but may be reloaded a while later.
+const RECENT_BLOCK_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// At most this many blocks are remembered, the oldest ones are forgotten first.
+const MAX_RECENT_BLOCKS: usize = 10_000;
+
+/// The reasons of the blocks clients recently ran into, keyed by client and domain. Blocks of
+/// aliases or addresses in the upstream answer cannot be told from the domain alone, so the
+/// block page and unblock requests go by what was actually decided.
+#[derive(Debug, Default)]
+pub struct RecentBlocks {
+    blocks: RwLock<HashMap<(IpAddr, String), (Instant, BlockReason)>>,
+}
+
+impl RecentBlocks {
+    pub async fn add(&self, client: IpAddr, domain: String, reason: BlockReason) {
+        let mut blocks = self.blocks.write().await;
+        if blocks.len() >= MAX_RECENT_BLOCKS {
+            blocks.retain(|_, (blocked_at, _)| blocked_at.elapsed() < RECENT_BLOCK_TTL);
+        }
+        if blocks.len() >= MAX_RECENT_BLOCKS {
+            let oldest = blocks
+                .iter()
+                .min_by_key(|(_, (blocked_at, _))| *blocked_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                blocks.remove(&oldest);
+            }
+        }
+        blocks.insert((client, domain), (Instant::now(), reason));
+    }
+
+    /// Why the domain was last blocked for the client, if that was recently.
+    pub async fn get(&self, client: IpAddr, domain: &str) -> Option<BlockReason> {
+        self.blocks
+            .read()
+            .await
+            .get(&(client, domain.to_string()))
+            .filter(|(blocked_at, _)| blocked_at.elapsed() < RECENT_BLOCK_TTL)
+            .map(|(_, reason)| reason.clone())
+    }
+}
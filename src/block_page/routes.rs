@@ -0,0 +1,123 @@
+use std::net::IpAddr;
+
+use crate::block_list::{normalize_domain, Explanation};
+use crate::http::{html_escape, percent_encode, Request, Response};
+use crate::{QueryService, Ready};
+
+pub(super) async fn handle(
+    request: Request,
+    client: IpAddr,
+    query_service: &QueryService<Ready>,
+) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        // A form rather than a link, so that merely prefetching or crawling the page does not
+        // send a request.
+        ("POST", "/request-unblock") => {
+            let Some(domain) = request
+                .query
+                .get("domain")
+                .and_then(|d| normalize_domain(d))
+            else {
+                return Response::text(400, "Missing domain");
+            };
+            query_service.request_unblock(&domain, client).await;
+            Response::html(
+                200,
+                page(
+                    "Request sent",
+                    &format!(
+                        "<p>Your request to unblock <b>{}</b> has been passed on.</p>",
+                        html_escape(&domain)
+                    ),
+                ),
+            )
+        }
+        ("GET", _) => match request
+            .headers
+            .get("host")
+            .and_then(|host| blocked_host(host))
+        {
+            Some(domain) => {
+                let explanation = query_service.explain_block(&domain, client).await;
+                Response::html(403, page("Blocked", &describe(&explanation)))
+            }
+            None => Response::html(
+                200,
+                page(
+                    "rustle",
+                    "<p>This is where rustle shows why a site was blocked.</p>",
+                ),
+            ),
+        },
+        _ => Response::text(405, "Method not allowed"),
+    }
+}
+
+/// The domain the browser asked for, unless it asked for us by address.
+fn blocked_host(host: &str) -> Option<String> {
+    if host.starts_with('[') {
+        return None;
+    }
+    let host = host.split(':').next().unwrap_or_default();
+    if host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+    normalize_domain(host)
+}
+
+fn describe(explanation: &Explanation) -> String {
+    let domain = html_escape(&explanation.domain);
+    let Some(reason) = explanation
+        .blocked_by
+        .as_ref()
+        .filter(|_| explanation.blocked)
+    else {
+        return format!(
+            "<p><b>{}</b> is not blocked (anymore). Try reloading the page in a bit.</p>",
+            domain
+        );
+    };
+
+    let mut html = format!("<p><b>{}</b> is blocked.</p><dl>", domain);
+    html.push_str(&format!("<dt>List</dt><dd>{}", html_escape(&reason.list)));
+    if !reason.category.is_empty() {
+        html.push_str(&format!(" ({})", html_escape(&reason.category)));
+    }
+    html.push_str("</dd>");
+    html.push_str(&format!(
+        "<dt>Rule</dt><dd>line {}: <code>{}</code></dd>",
+        reason.line,
+        html_escape(&reason.rule)
+    ));
+    if let Some(target) = &reason.cloaked_target {
+        html.push_str(&format!(
+            "<dt>Via</dt><dd>CNAME {}</dd>",
+            html_escape(target)
+        ));
+    }
+    if let Some(ip) = &reason.resolved_ip {
+        html.push_str(&format!("<dt>Address</dt><dd>{}</dd>", ip));
+    }
+    if let Some(group) = &explanation.group {
+        html.push_str(&format!("<dt>Group</dt><dd>{}</dd>", html_escape(group)));
+    }
+    html.push_str("</dl>");
+    html.push_str(&format!(
+        "<form method=\"post\" action=\"/request-unblock?domain={}\">\
+         <button>Request unblocking</button></form>",
+        percent_encode(&explanation.domain)
+    ));
+    html
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\
+         <html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>body {{ font-family: sans-serif; max-width: 40em; margin: 4em auto; }} \
+         dt {{ font-weight: bold; }}</style></head>\
+         <body><h1>{title}</h1>{body}</body></html>",
+        title = html_escape(title),
+        body = body
+    )
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use tokio::sync::RwLock;
+
+use crate::block_list::BlockReason;
+
+/// Only the most recent requests are kept around.
+const MAX_UNBLOCK_REQUESTS: usize = 1000;
+
+/// A user asking, by way of the block page, for a domain to be unblocked.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnblockRequest {
+    pub domain: String,
+    pub client: IpAddr,
+    pub group: Option<String>,
+    pub blocked_by: Option<BlockReason>,
+    pub requested_at: DateTime<Local>,
+}
+
+/// Unblock requests waiting for someone to look at them. Asking again for the same domain from
+/// the same client only refreshes the earlier request.
+#[derive(Debug, Default)]
+pub struct UnblockRequests {
+    requests: RwLock<Vec<UnblockRequest>>,
+}
+
+impl UnblockRequests {
+    pub async fn add(&self, request: UnblockRequest) {
+        let mut requests = self.requests.write().await;
+        requests
+            .retain(|earlier| earlier.domain != request.domain || earlier.client != request.client);
+        requests.push(request);
+        if requests.len() > MAX_UNBLOCK_REQUESTS {
+            let excess = requests.len() - MAX_UNBLOCK_REQUESTS;
+            requests.drain(..excess);
+        }
+    }
+
+    pub async fn all(&self) -> Vec<UnblockRequest> {
+        self.requests.read().await.clone()
+    }
+}
@@ -0,0 +1,45 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::local_zone::LocalRecord;
+use crate::policy::{ClientGroup, DohPolicy, ForwardRule, QTypeRule};
+
+/// Policies on top of the block lists, read from the json file given by `--config`.
+/// Every section is optional.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub qtype_rules: Vec<QTypeRule>,
+    pub groups: Vec<ClientGroup>,
+    /// Forces search engines into safe search for clients in no group.
+    pub safe_search: bool,
+    /// Keeps clients from resolving over encrypted DNS behind our back.
+    pub doh: DohPolicy,
+    pub local_records: Vec<LocalRecord>,
+    /// Hosts files served as local records, `/etc/hosts` if left out.
+    pub hosts_files: Option<Vec<PathBuf>>,
+    pub forwarding: Vec<ForwardRule>,
+    /// dnsmasq config files whose `server=/domain/address` lines are added to `forwarding`.
+    pub forwarding_imports: Vec<PathBuf>,
+}
+
+impl Config {
+    pub async fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let content = tokio::fs::read(path).await?;
+        let mut config: Config = serde_json::from_slice(&content)?;
+        for import in &config.forwarding_imports {
+            let content = tokio::fs::read_to_string(import).await?;
+            for line in content.lines().map(str::trim) {
+                if !line.starts_with("server=/") {
+                    continue;
+                }
+                match ForwardRule::from_dnsmasq(line) {
+                    Ok(rule) => config.forwarding.push(rule),
+                    // TODO: log this
+                    Err(e) => println!("Skipping {}: {}", import.display(), e),
+                }
+            }
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,206 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Request heads bigger than this are refused. Nothing we serve needs more.
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+/// Clients get this long to send the request head, so slow or idle ones do not hold on to their
+/// connection (and task) forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Just enough of HTTP/1.1 to serve a handful of endpoints.
+/// Every connection handles a single request and is closed afterwards.
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Keyed by lower cased name.
+    pub headers: HashMap<String, String>,
+}
+
+impl Request {
+    pub async fn read_from(
+        stream: &mut TcpStream,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut buf = Vec::new();
+        let header_end = tokio::time::timeout(READ_TIMEOUT, read_head(stream, &mut buf))
+            .await
+            .map_err(|_| "Timed out reading the request")??;
+
+        let head = std::str::from_utf8(&buf[..header_end])?;
+        let mut lines = head.split("\r\n");
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().ok_or("Missing method")?.to_string();
+        let target = parts.next().ok_or("Missing request target")?;
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, parse_query(query)),
+            None => (target, HashMap::new()),
+        };
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        Ok(Request {
+            method,
+            path: percent_decode(path),
+            query,
+            headers,
+        })
+    }
+}
+
+/// Reads until the end of the request head and returns where it ends.
+async fn read_head(
+    stream: &mut TcpStream,
+    buf: &mut Vec<u8>,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let mut chunk = [0; 4096];
+    loop {
+        let size = stream.read(&mut chunk).await?;
+        if size == 0 {
+            return Err("Connection closed before request was complete".into());
+        }
+        buf.extend_from_slice(&chunk[..size]);
+        if let Some(idx) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            return Ok(idx);
+        }
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Err("Request header too large".into());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec_pretty(value) {
+            Ok(body) => Response {
+                status,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Response::text(500, &e.to_string()),
+        }
+    }
+
+    pub fn text(status: u16, text: &str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: text.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn html(status: u16, html: String) -> Self {
+        Response {
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: html.into_bytes(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Response::text(404, "Not found")
+    }
+
+    pub async fn write_to(
+        self,
+        stream: &mut TcpStream,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason_phrase(self.status),
+            self.content_type,
+            self.body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&self.body).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (percent_decode(key), percent_decode(value)),
+            None => (percent_decode(pair), String::new()),
+        })
+        .collect()
+}
+
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => decoded.push(b' '),
+            b'%' if idx + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[idx + 1..idx + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        idx += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Makes text safe to put into html.
+pub fn html_escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&#39;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
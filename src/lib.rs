@@ -1,3 +1,6 @@
+// DNS record types are spelled the way the RFCs spell them (i.e. `QType::AAAA`).
+#![allow(clippy::upper_case_acronyms)]
+
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -5,12 +8,30 @@ use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
+pub mod admin;
+mod block_list;
+pub mod block_page;
+mod config;
+mod http;
+mod local_zone;
+mod policy;
 mod query_service;
 
-pub use query_service::{QueryService, Ready, Response};
+pub use block_list::{
+    BlockListReport, BlockListSource, BlockReason, Explanation, ListFormat, SourceLocation,
+    SourceStatus, Validation,
+};
+pub use config::Config;
+pub use query_service::{BlockingMode, QueryService, Ready, Response};
+
+pub type OpaqueError = Box<dyn std::error::Error + Send + Sync>;
+pub type LongRunningTaskType = JoinHandle<Result<(), OpaqueError>>;
 
-type OpaqueError = Box<dyn std::error::Error + Send + Sync>;
-type LongRunningTaskType = JoinHandle<Result<(), OpaqueError>>;
+/// A query forwarded upstream, waiting for its response.
+struct PendingQuery {
+    client_addr: SocketAddr,
+    query: Vec<u8>,
+}
 
 /// Provider for task that listens for external messages.
 /// Upon receiving a message (which would be a UDP packet, because it's a DNS query), it spawns a
@@ -27,10 +48,13 @@ pub async fn get_input_tasks(
     // This should be fine since historically DNS queries operated within the UDP packet limit,
     // which is only 512 bytes
     let mut buf = [0; 1024];
-    let parking_lot: Arc<RwLock<HashMap<u16, SocketAddr>>> = Arc::new(RwLock::new(HashMap::new()));
+    let parking_lot: Arc<RwLock<HashMap<u16, PendingQuery>>> =
+        Arc::new(RwLock::new(HashMap::new()));
     let parking_lot_clone = parking_lot.clone();
     // We'll use a different socket for now for subrequest. Technically we do not need to do that.
     let socket_subrequest_clone = socket_subrequest.clone();
+    let socket_orig_sender_clone = socket_orig_sender.clone();
+    let query_service_clone = query_service.clone();
 
     let router_addr = Arc::new(router_addr.to_string());
     let main_listener_task = tokio::spawn(async move {
@@ -50,17 +74,35 @@ pub async fn get_input_tasks(
                 println!("received content... processing");
 
                 // call byte handler to decode message and run a query
-                match query_service.process_bytes(&content).await? {
-                    Response::Hit(bytes) => {}
-                    Response::Miss(id) => {
-                        {
-                            parking_lot.write().await.insert(id, addr);
+                let (id, forwarded, upstream) =
+                    match query_service.process_bytes(&content, addr).await? {
+                        Response::Hit(bytes) | Response::Blocked(bytes, _) => {
+                            _ = socket_orig_sender.send_to(&bytes, addr).await?;
+                            return Ok(());
                         }
-                        _ = socket_subrequest
-                            .send_to(&content, router_addr.as_str())
-                            .await?;
-                    }
+                        Response::Dropped(_) => return Ok(()),
+                        Response::Miss(id, upstream) => (id, content.clone(), upstream),
+                        Response::Rewritten(id, rewritten, upstream) => (id, rewritten, upstream),
+                    };
+
+                // The original query is kept, that is what the response is an answer to.
+                {
+                    parking_lot.write().await.insert(
+                        id,
+                        PendingQuery {
+                            client_addr: addr,
+                            query: content,
+                        },
+                    );
                 }
+                match upstream {
+                    Some(upstream) => socket_subrequest.send_to(&forwarded, upstream).await?,
+                    None => {
+                        socket_subrequest
+                            .send_to(&forwarded, router_addr.as_str())
+                            .await?
+                    }
+                };
 
                 Ok::<(), OpaqueError>(())
             });
@@ -71,10 +113,40 @@ pub async fn get_input_tasks(
     });
 
     let subrequest_task = tokio::spawn(async move {
-        let mut buf = [0; 1024];
+        // Responses can be larger than queries, especially with EDNS.
+        let mut buf = [0; 4096];
         loop {
             let (size, addr) = socket_subrequest.recv_from(&mut buf).await?;
+            // TODO: log this
             println!("Reponse received from {}", addr);
+            if size < 2 {
+                continue;
+            }
+            let content = buf[..size].to_vec();
+            let id = (content[0] as u16) << 8 | content[1] as u16;
+            let Some(pending) = parking_lot.write().await.remove(&id) else {
+                println!("No pending query for response {}", id);
+                continue;
+            };
+
+            let socket_orig_sender = socket_orig_sender_clone.clone();
+            let query_service = query_service_clone.clone();
+            tokio::spawn(async move {
+                match query_service
+                    .process_upstream_bytes(&pending.query, &content, pending.client_addr)
+                    .await?
+                {
+                    Response::Hit(bytes) | Response::Blocked(bytes, _) => {
+                        _ = socket_orig_sender
+                            .send_to(&bytes, pending.client_addr)
+                            .await?;
+                    }
+                    // Responses are never sent upstream again.
+                    Response::Miss(..) | Response::Rewritten(..) | Response::Dropped(_) => {}
+                }
+
+                Ok::<(), OpaqueError>(())
+            });
         }
         // TODO: Add shutdown routine
         #[allow(unreachable_code)]
@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use super::local_record::{LocalRecord, DEFAULT_TTL};
+use crate::block_list::{normalize_domain, ListFiles};
+use crate::query_service::RData;
+
+/// Hosts files (i.e. `/etc/hosts`) served as local records. The files are watched for changes
+/// and read again in the background whenever one of them changed, queries only ever look at the
+/// records read last.
+#[derive(Debug, Default)]
+pub struct HostsFiles {
+    paths: Vec<PathBuf>,
+    records: RwLock<Arc<Vec<LocalRecord>>>,
+}
+
+impl HostsFiles {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        HostsFiles {
+            paths,
+            records: RwLock::new(Arc::default()),
+        }
+    }
+
+    pub async fn records(&self) -> Arc<Vec<LocalRecord>> {
+        self.records.read().await.clone()
+    }
+
+    /// Reads the files again and puts their records in use.
+    pub async fn reload(&self) {
+        let records = Arc::new(self.read_all().await);
+        *self.records.write().await = records;
+    }
+
+    /// Reloads the files whenever they change, for good.
+    pub async fn watch(self: Arc<Self>) {
+        if self.paths.is_empty() {
+            return;
+        }
+        let mut files = ListFiles::new();
+        let watched = self
+            .paths
+            .iter()
+            .map(|path| (path.display().to_string(), path.clone()))
+            .collect();
+        files.watch(watched).await;
+        loop {
+            let changed = files.changed().await;
+            // TODO: log this
+            println!("Hosts file {} changed, reloading", changed.join(", "));
+            self.reload().await;
+        }
+    }
+
+    async fn read_all(&self) -> Vec<LocalRecord> {
+        let mut records = Vec::new();
+        for path in &self.paths {
+            match tokio::fs::read_to_string(path).await {
+                Ok(content) => records.extend(parse_hosts(&content)),
+                // TODO: log this
+                Err(e) => println!("Hosts file {} could not be read: {}", path.display(), e),
+            }
+        }
+        // TODO: log this
+        println!("Read {} records from hosts files", records.len());
+        records
+    }
+}
+
+/// Lines are an address followed by the names it goes by, the first of which is the canonical
+/// one: `192.168.1.10  nas.home nas`. Each name gets an A or AAAA record and the canonical name a
+/// PTR record, unless an earlier line already claimed the address.
+pub fn parse_hosts(content: &str) -> Vec<LocalRecord> {
+    let mut records = Vec::new();
+    let mut reversed = HashSet::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut columns = line.split_whitespace();
+        let Some(Ok(ip)) = columns.next().map(str::parse::<IpAddr>) else {
+            continue;
+        };
+        let names: Vec<String> = columns.filter_map(normalize_domain).collect();
+        let Some(canonical) = names.first() else {
+            continue;
+        };
+
+        if !ip.is_unspecified() && reversed.insert(ip) {
+            records.push(LocalRecord {
+                name: reverse_name(&ip),
+                ttl: DEFAULT_TTL,
+                data: RData::PTR(canonical.clone()),
+            });
+        }
+        let data = match ip {
+            IpAddr::V4(ip) => RData::A(ip),
+            IpAddr::V6(ip) => RData::AAAA(ip),
+        };
+        records.extend(names.into_iter().map(|name| LocalRecord {
+            name,
+            ttl: DEFAULT_TTL,
+            data: data.clone(),
+        }));
+    }
+    records
+}
+
+/// The name PTR records of the address live under, i.e. `10.1.168.192.in-addr.arpa`.
+pub fn reverse_name(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::with_capacity(72);
+            for octet in ip.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", octet & 0x0f, octet >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Deserialize;
+
+use crate::block_list::normalize_domain;
+use crate::query_service::{QType, RData};
+
+pub(crate) const DEFAULT_TTL: u32 = 300;
+
+/// A record served straight from the local zone, i.e.
+/// `{ "name": "nas.home", "type": "A", "value": "192.168.1.10" }`.
+/// A name starting with `*.` covers every subdomain of the rest (but not the rest itself).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "LocalRecordSpec")]
+pub struct LocalRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub data: RData,
+}
+
+/// A local record the way it is written in the config.
+#[derive(Deserialize)]
+struct LocalRecordSpec {
+    name: String,
+    #[serde(rename = "type")]
+    q_type: QType,
+    value: String,
+    #[serde(default)]
+    ttl: Option<u32>,
+}
+
+impl LocalRecord {
+    /// The parent domain of a wildcard record.
+    pub fn wildcard_parent(&self) -> Option<&str> {
+        self.name.strip_prefix("*.")
+    }
+}
+
+impl TryFrom<LocalRecordSpec> for LocalRecord {
+    type Error = String;
+
+    fn try_from(spec: LocalRecordSpec) -> Result<Self, Self::Error> {
+        let name = match spec.name.strip_prefix("*.") {
+            Some(parent) => normalize_domain(parent).map(|parent| format!("*.{}", parent)),
+            None => normalize_domain(&spec.name),
+        }
+        .ok_or(format!("{} is not a valid name", spec.name))?;
+        let value = spec.value.trim();
+        let invalid = || format!("{} is not a valid {} value", value, spec.q_type);
+        let data = match spec.q_type {
+            QType::A => RData::A(value.parse().map_err(|_| invalid())?),
+            QType::AAAA => RData::AAAA(value.parse().map_err(|_| invalid())?),
+            QType::CNAME => RData::CNAME(normalize_domain(value).ok_or_else(invalid)?),
+            QType::PTR => RData::PTR(normalize_domain(value).ok_or_else(invalid)?),
+            QType::TXT => RData::TXT(spec.value.clone()),
+            // Written the way zone files do: `priority weight port target`.
+            QType::SRV => match value.split_whitespace().collect::<Vec<_>>()[..] {
+                [priority, weight, port, target] => RData::SRV {
+                    priority: priority.parse().map_err(|_| invalid())?,
+                    weight: weight.parse().map_err(|_| invalid())?,
+                    port: port.parse().map_err(|_| invalid())?,
+                    target: normalize_domain(target).ok_or_else(invalid)?,
+                },
+                _ => return Err(invalid()),
+            },
+            q_type => return Err(format!("{} records can not be served locally", q_type)),
+        };
+        Ok(LocalRecord {
+            name,
+            ttl: spec.ttl.unwrap_or(DEFAULT_TTL),
+            data,
+        })
+    }
+}
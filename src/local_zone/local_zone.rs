@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use crate::block_list::is_same_or_subdomain;
+use crate::policy::SAFE_SEARCH_RECORDS;
+use crate::query_service::{QType, RData, ResourceRecord};
+
+use super::hosts_file::HostsFiles;
+use super::local_record::LocalRecord;
+
+/// Aliases within the zone are followed at most this many times, which also stops loops.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// What the zone has to say about a name.
+#[derive(Debug)]
+pub struct LocalAnswer {
+    pub records: Vec<ResourceRecord>,
+    /// Set when the aliases lead out of the zone: the name whose upstream answer completes the
+    /// answer, after the aliases.
+    pub external_target: Option<String>,
+}
+
+/// Records answered without going upstream: the configured ones and those of the hosts files.
+#[derive(Debug, Default)]
+pub struct LocalZone {
+    records: Vec<LocalRecord>,
+    hosts_files: Arc<HostsFiles>,
+}
+
+impl LocalZone {
+    pub fn new(records: Vec<LocalRecord>, hosts_files: HostsFiles) -> Self {
+        LocalZone {
+            records,
+            hosts_files: Arc::new(hosts_files),
+        }
+    }
+
+    /// The hosts files, to be read and kept up to date.
+    pub fn hosts_files(&self) -> Arc<HostsFiles> {
+        self.hosts_files.clone()
+    }
+
+    /// The answer to a query for the name, `None` if the name is not part of the zone.
+    /// An empty answer means the name exists but has no records of the queried type. Aliases
+    /// are followed as long as their targets are in the zone too, the target they end up at
+    /// otherwise is left to be looked up upstream. With `safe_search` the aliases of the safe
+    /// search providers are part of the zone too.
+    pub async fn lookup(
+        &self,
+        name: &str,
+        q_type: QType,
+        safe_search: bool,
+    ) -> Option<LocalAnswer> {
+        let hosts_records = self.hosts_files.records().await;
+        let safe_search_records = SAFE_SEARCH_RECORDS.iter().filter(|_| safe_search);
+        let records: Vec<&LocalRecord> = self
+            .records
+            .iter()
+            .chain(hosts_records.iter())
+            .chain(safe_search_records)
+            .collect();
+        let mut answers = Vec::new();
+        let mut name = name.to_string();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let Some(records) = records_for(&records, &name) else {
+                break;
+            };
+            let matching: Vec<ResourceRecord> = records
+                .iter()
+                .filter(|record| q_type == QType::ANY || record.data.q_type() == q_type)
+                .map(|record| ResourceRecord {
+                    name: name.clone(),
+                    ttl: record.ttl,
+                    data: record.data.clone(),
+                })
+                .collect();
+            if !matching.is_empty() {
+                answers.extend(matching);
+                break;
+            }
+            let Some((alias, target)) = records.iter().find_map(|record| match &record.data {
+                RData::CNAME(target) => Some((record, target)),
+                _ => None,
+            }) else {
+                break;
+            };
+            answers.push(ResourceRecord {
+                name: name.clone(),
+                ttl: alias.ttl,
+                data: alias.data.clone(),
+            });
+            name = target.clone();
+        }
+
+        let in_zone = records_for(&records, &name).is_some();
+        if answers.is_empty() && !in_zone {
+            return None;
+        }
+        Some(LocalAnswer {
+            records: answers,
+            external_target: (!in_zone).then_some(name),
+        })
+    }
+}
+
+/// Records of the exact name or, failing that, of the closest wildcard covering it.
+fn records_for<'a>(records: &[&'a LocalRecord], name: &str) -> Option<Vec<&'a LocalRecord>> {
+    let exact: Vec<&LocalRecord> = records
+        .iter()
+        .copied()
+        .filter(|record| record.name == name)
+        .collect();
+    if !exact.is_empty() {
+        return Some(exact);
+    }
+
+    let closest = records
+        .iter()
+        .filter_map(|record| record.wildcard_parent())
+        .filter(|parent| *parent != name && is_same_or_subdomain(name, parent))
+        .max_by_key(|parent| parent.len())?;
+    Some(
+        records
+            .iter()
+            .copied()
+            .filter(|record| record.wildcard_parent() == Some(closest))
+            .collect(),
+    )
+}
@@ -0,0 +1,10 @@
+mod hosts_file;
+mod local_record;
+#[allow(clippy::module_inception)]
+mod local_zone;
+mod special_use;
+
+pub use hosts_file::*;
+pub use local_record::*;
+pub use local_zone::*;
+pub use special_use::*;
@@ -0,0 +1,87 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::LazyLock;
+
+use super::local_record::DEFAULT_TTL;
+use crate::block_list::is_same_or_subdomain;
+use crate::query_service::{QType, RData, ResourceRecord};
+
+/// Names that mean nothing outside of the local network (RFC 6761) and reverse zones of
+/// addresses that are not globally routed (RFC 6303, RFC 7793). Asking the upstream server about
+/// them only leaks what the network looks like.
+static NON_EXISTENT_ZONES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let mut zones: Vec<String> = [
+        "invalid",
+        "test",
+        // Belongs to multicast DNS (RFC 6762).
+        "local",
+        "0.in-addr.arpa",
+        "10.in-addr.arpa",
+        "127.in-addr.arpa",
+        "254.169.in-addr.arpa",
+        "168.192.in-addr.arpa",
+        "2.0.192.in-addr.arpa",
+        "100.51.198.in-addr.arpa",
+        "113.0.203.in-addr.arpa",
+        "255.255.255.255.in-addr.arpa",
+        // :: and ::1
+        "0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa",
+        "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.ip6.arpa",
+        // Unique local addresses (fd00::/8)
+        "d.f.ip6.arpa",
+        // Link local addresses (fe80::/10)
+        "8.e.f.ip6.arpa",
+        "9.e.f.ip6.arpa",
+        "a.e.f.ip6.arpa",
+        "b.e.f.ip6.arpa",
+        // Documentation addresses (2001:db8::/32)
+        "8.b.d.0.1.0.0.2.ip6.arpa",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+    // 172.16.0.0/12
+    zones.extend((16..=31).map(|octet| format!("{}.172.in-addr.arpa", octet)));
+    // Shared address space (100.64.0.0/10)
+    zones.extend((64..=127).map(|octet| format!("{}.100.in-addr.arpa", octet)));
+    zones
+});
+
+/// How a special-use name is answered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpecialUse {
+    /// `localhost` and its subdomains are the loopback address.
+    Loopback,
+    /// The name does not exist, whatever the upstream server might say.
+    NonExistent,
+}
+
+impl SpecialUse {
+    pub fn of(domain: &str) -> Option<Self> {
+        if is_same_or_subdomain(domain, "localhost") {
+            return Some(SpecialUse::Loopback);
+        }
+        NON_EXISTENT_ZONES
+            .iter()
+            .any(|zone| is_same_or_subdomain(domain, zone))
+            .then_some(SpecialUse::NonExistent)
+    }
+
+    /// The records to answer with. Names that do not exist have none.
+    pub fn answers(&self, name: &str, q_type: QType) -> Vec<ResourceRecord> {
+        let data = match self {
+            SpecialUse::Loopback => vec![
+                RData::A(Ipv4Addr::LOCALHOST),
+                RData::AAAA(Ipv6Addr::LOCALHOST),
+            ],
+            SpecialUse::NonExistent => vec![],
+        };
+        data.into_iter()
+            .filter(|data| q_type == QType::ANY || data.q_type() == q_type)
+            .map(|data| ResourceRecord {
+                name: name.to_string(),
+                ttl: DEFAULT_TTL,
+                data,
+            })
+            .collect()
+    }
+}
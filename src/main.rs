@@ -1,12 +1,15 @@
 use futures::{future::select_all, future::FutureExt};
+use rustle::admin::{self, AdminClient};
+use rustle::block_page;
 use rustle::get_input_tasks;
-use rustle::QueryService;
+use rustle::{BlockListSource, BlockingMode, Config, ListFormat, QueryService};
 use socket2::{Domain, Protocol, Socket, Type};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::Arc;
 use structopt::StructOpt;
 use tokio::net::UdpSocket;
+use tokio::signal::unix::{signal, SignalKind};
 
 #[derive(StructOpt)]
 struct Opt {
@@ -15,19 +18,239 @@ struct Opt {
 
     #[structopt(default_value = "2001:558:feed::1:53", short, long)]
     router_addr: String,
+
+    /// Json file with the block lists to use. EasyList is used if none is given.
+    #[structopt(short, long, parse(from_os_str))]
+    sources: Option<PathBuf>,
+
+    /// Json file with the policies to apply on top of the block lists.
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// How blocked queries are answered: `null`, `nxdomain`, `nodata`, `refused` or the ip address
+    /// of the block page.
+    #[structopt(default_value = "null", short, long)]
+    blocking_mode: BlockingMode,
+
+    /// Address of the admin api. The commands below talk to the instance listening on it.
+    #[structopt(default_value = "127.0.0.1:8081", short, long)]
+    admin_addr: String,
+
+    /// How many downloads of each list to keep around to roll back to.
+    #[structopt(default_value = "5", long)]
+    snapshot_retention: usize,
+
+    /// Address of the block page, served when blocked domains are answered with an ip address.
+    #[structopt(default_value = "[::]:80", long)]
+    block_page_addr: String,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands for a running instance. Without one rustle starts serving.
+#[derive(StructOpt)]
+enum Command {
+    /// Shows the block lists and how their updates are going.
+    Lists,
+    /// Adds a block list, or changes the given settings of the list with the name. Changes last
+    /// until the instance is restarted.
+    AddList {
+        name: String,
+
+        /// A http(s) url or a local path, needed for new lists.
+        location: Option<String>,
+
+        /// `adblock`, `hosts` or `domains`.
+        #[structopt(long)]
+        format: Option<ListFormat>,
+
+        #[structopt(long)]
+        category: Option<String>,
+
+        #[structopt(long)]
+        refresh_interval_secs: Option<u64>,
+    },
+    /// Stops using a block list until the instance is restarted.
+    RemoveList { list: String },
+    /// Turns a block list back on.
+    Enable { list: String },
+    /// Turns a block list off: it neither blocks nor gets updated.
+    Disable { list: String },
+    /// Tells which list and rule block a domain.
+    Explain {
+        domain: String,
+
+        /// Look at the domain as it applies to this client's group.
+        #[structopt(long)]
+        client: Option<IpAddr>,
+    },
+    /// Pauses blocking for a while, i.e. `5m`. It resumes on its own afterwards.
+    Pause {
+        duration: String,
+
+        /// Only pause blocking for this group.
+        #[structopt(long)]
+        group: Option<String>,
+    },
+    /// Ends a pause early.
+    Resume {
+        #[structopt(long)]
+        group: Option<String>,
+    },
+    /// Shows the stored downloads of a list, newest first.
+    Snapshots { list: String },
+    /// Shows the domains the last update of a list added and removed.
+    Diff {
+        list: String,
+
+        /// Show the update that produced this snapshot instead.
+        #[structopt(long)]
+        snapshot: Option<String>,
+    },
+    /// Puts a stored download of a list back in use until the list is refreshed next.
+    Rollback {
+        list: String,
+
+        /// The snapshot to roll back to, the one before the snapshot in use if left out.
+        #[structopt(long)]
+        to: Option<String>,
+    },
+    /// Refreshes the block lists right away rather than when they are due. Sending SIGHUP to a
+    /// running instance does the same.
+    Refresh {
+        /// Only refresh this list.
+        #[structopt(long)]
+        list: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let Opt { port, router_addr } = Opt::from_args();
+    let Opt {
+        port,
+        router_addr,
+        sources,
+        config,
+        blocking_mode,
+        admin_addr,
+        block_page_addr,
+        snapshot_retention,
+        command,
+    } = Opt::from_args();
+
+    if let Some(command) = command {
+        let client = AdminClient::new(&admin_addr);
+        match command {
+            Command::Lists => {
+                for list in client.lists().await? {
+                    println!("{}", list);
+                }
+            }
+            Command::AddList {
+                name,
+                location,
+                format,
+                category,
+                refresh_interval_secs,
+            } => {
+                let source = client
+                    .add_list(
+                        &name,
+                        location.as_deref(),
+                        format,
+                        category.as_deref(),
+                        refresh_interval_secs,
+                    )
+                    .await?;
+                println!(
+                    "{} ({}) set to {}",
+                    source.name,
+                    source.format,
+                    String::from(source.location)
+                );
+            }
+            Command::RemoveList { list } => {
+                client.remove_list(&list).await?;
+                println!("{} removed", list);
+            }
+            Command::Enable { list } => {
+                client.set_list_enabled(&list, true).await?;
+                println!("{} enabled", list);
+            }
+            Command::Disable { list } => {
+                client.set_list_enabled(&list, false).await?;
+                println!("{} disabled", list);
+            }
+            Command::Explain {
+                domain,
+                client: client_addr,
+            } => println!("{}", client.explain(&domain, client_addr).await?),
+            Command::Pause { duration, group } => {
+                println!("{}", client.pause(&duration, group.as_deref()).await?)
+            }
+            Command::Resume { group } => {
+                client.resume(group.as_deref()).await?;
+                println!("Blocking resumed");
+            }
+            Command::Snapshots { list } => {
+                let in_use = client
+                    .lists()
+                    .await?
+                    .into_iter()
+                    .find(|report| report.source.name == list)
+                    .and_then(|report| report.status.snapshot);
+                for snapshot in client.snapshots(&list).await? {
+                    let marker = match Some(&snapshot.path) == in_use.as_ref() {
+                        true => " (in use)",
+                        false => "",
+                    };
+                    println!("{}{}", snapshot.id, marker);
+                }
+            }
+            Command::Diff { list, snapshot } => {
+                println!("{}", client.diff(&list, snapshot.as_deref()).await?)
+            }
+            Command::Rollback { list, to } => {
+                let snapshot = client.rollback(&list, to.as_deref()).await?;
+                println!("{} rolled back to {}", list, snapshot.id);
+            }
+            Command::Refresh { list } => {
+                client.refresh(list.as_deref()).await?;
+                println!("Refresh scheduled");
+            }
+        }
+        return Ok(());
+    }
 
     let main_addr = format!("[::]:{}", port);
     let sub_addr = "[::]:0";
 
     tokio::fs::create_dir_all("var/db").await?;
-    tokio::fs::write("var/db/init.txt", "/something/something/").await?;
 
-    let mut query_service = QueryService::new(PathBuf::from("var/db/init.txt"))
+    let sources = match sources {
+        Some(path) => BlockListSource::load_all(&path).await?,
+        None => vec![BlockListSource::default()],
+    };
+    let config = match config {
+        Some(path) => Config::load(&path).await?,
+        None => Config::default(),
+    };
+
+    let mut query_service = QueryService::new(PathBuf::from("var/db"), sources)
+        .with_blocking_mode(blocking_mode)
+        .with_snapshot_retention(snapshot_retention)
+        .with_qtype_rules(config.qtype_rules)
+        .with_client_groups(config.groups)
+        .with_local_zone(
+            config.local_records,
+            config
+                .hosts_files
+                .unwrap_or_else(|| vec![PathBuf::from("/etc/hosts")]),
+        )
+        .with_forward_rules(config.forwarding)
+        .with_safe_search(config.safe_search)
+        .with_doh_policy(config.doh)
         .index_db()
         .await?
         .register_for_periodic_update()?;
@@ -35,6 +258,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .gib_update_task_handle()
         .ok_or("Update task handle is None")?;
 
+    let update_task = async {
+        // TODO: log this
+        match update_task_handle.await {
+            Ok(Ok(())) => println!("Update task exited normally"),
+            Ok(Err(e)) => println!("Update task exited with error: {:?}", e),
+            Err(e) => println!("Update task failed: {:?}", e),
+        }
+        println!("Block lists are no longer updated");
+        std::future::pending::<()>().await
+    };
+
     let cpu_num = num_cpus::get();
     let query_service = Arc::new(query_service);
     let mut main_listener_tasks = Vec::new();
@@ -45,10 +279,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let (main_socket, sub_socket) = {
             let main_socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
             main_socket.set_reuse_port(true)?;
+            main_socket.set_nonblocking(true)?;
             main_socket.bind(&main_addr.parse::<SocketAddr>()?.into())?;
 
             let sub_socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
             sub_socket.set_reuse_port(true)?;
+            sub_socket.set_nonblocking(true)?;
             sub_socket.bind(&sub_addr.parse::<SocketAddr>()?.into())?;
             (
                 UdpSocket::from_std(main_socket.into())?,
@@ -67,18 +303,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         subrequest_tasks.push(subrequest_task);
     }
 
+    let admin_task = admin::serve(&admin_addr, query_service.clone()).await?;
+    let refresh_signal_task = {
+        let query_service = query_service.clone();
+        let mut hangup = signal(SignalKind::hangup())?;
+        async move {
+            while hangup.recv().await.is_some() {
+                // TODO: log this
+                println!("SIGHUP received, refreshing block lists");
+                query_service.refresh(None).await;
+            }
+        }
+    };
+    let block_page_task = match query_service.serves_block_page() {
+        true => Some(block_page::serve(&block_page_addr, query_service.clone()).await?),
+        false => None,
+    };
+    let block_page_task = async {
+        match block_page_task {
+            Some(block_page_task) => block_page_task.await,
+            None => std::future::pending().await,
+        }
+    };
+
     let main_listener_tasks = select_all(main_listener_tasks).fuse();
     let subrequest_tasks = select_all(subrequest_tasks).fuse();
 
     tokio::select! {
         _ = main_listener_tasks => {}
         _ = subrequest_tasks => {}
-        update_res = update_task_handle => {
-            match update_res {
-                Ok(_) => println!("Update task exited normally"),
-                Err(e) => println!("Update task exited with error: {:?}", e),
-            }
+        admin_res = admin_task => {
+            println!("Admin task exited: {:?}", admin_res);
+        }
+        block_page_res = block_page_task => {
+            println!("Block page task exited: {:?}", block_page_res);
+        }
+        _ = refresh_signal_task => {
+            println!("Refresh signal task exited");
         }
+        // Without updates the lists in use go stale, which is no reason to stop answering.
+        _ = update_task => {}
     }
 
     Ok(())
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// How long a read of the kernel's neighbor table is trusted for.
+const ARP_TABLE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MacAddr([u8; 6]);
+
+impl FromStr for MacAddr {
+    type Err = String;
+
+    /// Accepts `aa:bb:cc:dd:ee:ff` as well as `aa-bb-cc-dd-ee-ff`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0; 6];
+        let mut parts = s.split([':', '-']);
+        for octet in octets.iter_mut() {
+            *octet = parts
+                .next()
+                .and_then(|part| u8::from_str_radix(part, 16).ok())
+                .ok_or(format!("{} is not a mac address", s))?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("{} is not a mac address", s));
+        }
+        Ok(MacAddr(octets))
+    }
+}
+
+/// Maps client addresses to hardware addresses, by way of `/proc/net/arp`.
+/// Only IPv4 neighbors are listed there, so IPv6 clients can not be matched by mac address.
+#[derive(Debug, Default)]
+pub struct ArpTable {
+    entries: RwLock<Option<(Instant, HashMap<IpAddr, MacAddr>)>>,
+}
+
+impl ArpTable {
+    pub async fn lookup(&self, ip: &IpAddr) -> Option<MacAddr> {
+        {
+            let entries = self.entries.read().await;
+            if let Some((read_at, entries)) = entries.as_ref() {
+                if read_at.elapsed() < ARP_TABLE_TTL {
+                    return entries.get(ip).copied();
+                }
+            }
+        }
+
+        let entries = match tokio::fs::read_to_string("/proc/net/arp").await {
+            Ok(content) => parse_proc_net_arp(&content),
+            Err(e) => {
+                // TODO: log this
+                println!("Arp table could not be read: {}", e);
+                HashMap::new()
+            }
+        };
+        let mac_addr = entries.get(ip).copied();
+        *self.entries.write().await = Some((Instant::now(), entries));
+        mac_addr
+    }
+}
+
+/// The format is a header line followed by lines like:
+/// `192.168.1.10     0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0`
+fn parse_proc_net_arp(content: &str) -> HashMap<IpAddr, MacAddr> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let ip = columns.next()?.parse().ok()?;
+            let mac_addr = columns.nth(2)?.parse().ok()?;
+            Some((ip, mac_addr))
+        })
+        .collect()
+}
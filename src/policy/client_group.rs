@@ -0,0 +1,123 @@
+use chrono::Utc;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+use super::arp_table::{ArpTable, MacAddr};
+use super::schedule::Schedule;
+use crate::block_list::{is_same_or_subdomain, normalize_domain, IpCidr};
+use crate::query_service::BlockingMode;
+
+/// How a client is recognized: by address, by address range or by hardware address.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ClientMatcher {
+    Cidr(IpCidr),
+    Mac(MacAddr),
+}
+
+impl TryFrom<String> for ClientMatcher {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if let Ok(cidr) = s.parse() {
+            return Ok(ClientMatcher::Cidr(cidr));
+        }
+        s.parse()
+            .map(ClientMatcher::Mac)
+            .map_err(|_| format!("{} is neither an ip, a cidr range nor a mac address", s))
+    }
+}
+
+/// A set of clients sharing a policy. Clients in no group get the defaults: every enabled list,
+/// no allowlist and the global blocking mode.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ClientGroup {
+    pub name: String,
+    pub clients: Vec<ClientMatcher>,
+    /// Names of the block lists applying to the group. Lists that are disabled altogether stay
+    /// disabled. All enabled lists apply if left out.
+    #[serde(default)]
+    pub lists: Option<Vec<String>>,
+    /// Domains (and their subdomains) never blocked for the group.
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    #[serde(default)]
+    pub blocking_mode: Option<BlockingMode>,
+    /// Overrides the global `safe_search` setting for the group.
+    #[serde(default)]
+    pub safe_search: Option<bool>,
+    /// Blocks every domain not on the allowlist, i.e. for bedtime.
+    #[serde(default)]
+    pub block_all: bool,
+    /// When the group is in effect. Its clients fall through to the next matching group (or the
+    /// defaults) the rest of the time.
+    #[serde(default)]
+    pub schedule: Schedule,
+}
+
+impl ClientGroup {
+    /// The position (one based) and entry of the allowlist covering the domain, if any.
+    pub fn allows(&self, domain: &str) -> Option<(usize, &str)> {
+        self.allowlist
+            .iter()
+            .enumerate()
+            .find(|(_, allowed)| {
+                normalize_domain(allowed)
+                    .is_some_and(|allowed| is_same_or_subdomain(domain, &allowed))
+            })
+            .map(|(idx, allowed)| (idx + 1, allowed.as_str()))
+    }
+}
+
+/// The configured groups, consulted in order. A client belongs to the first active group matching
+/// it.
+#[derive(Debug, Default)]
+pub struct ClientGroups {
+    groups: Vec<ClientGroup>,
+    arp_table: ArpTable,
+}
+
+impl ClientGroups {
+    pub fn new(groups: Vec<ClientGroup>) -> Self {
+        ClientGroups {
+            groups,
+            arp_table: ArpTable::default(),
+        }
+    }
+
+    pub fn blocking_modes(&self) -> impl Iterator<Item = BlockingMode> + '_ {
+        self.groups.iter().filter_map(|group| group.blocking_mode)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.groups.iter().any(|group| group.name == name)
+    }
+
+    pub async fn resolve(&self, client: &IpAddr) -> Option<&ClientGroup> {
+        // IPv4 clients show up as mapped addresses on the dual stack socket.
+        let client = client.to_canonical();
+        let mut mac_addr = None;
+        let now = Utc::now();
+        for group in self
+            .groups
+            .iter()
+            .filter(|group| group.schedule.is_active(now))
+        {
+            for matcher in &group.clients {
+                let is_match = match matcher {
+                    ClientMatcher::Cidr(cidr) => cidr.contains(&client),
+                    ClientMatcher::Mac(expected) => {
+                        if mac_addr.is_none() {
+                            mac_addr = Some(self.arp_table.lookup(&client).await);
+                        }
+                        mac_addr.flatten().as_ref() == Some(expected)
+                    }
+                };
+                if is_match {
+                    return Some(group);
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Deserialize;
+use std::sync::LazyLock;
+
+use crate::block_list::{is_same_or_subdomain, normalize_domain};
+
+/// Domains clients look up to decide whether to bypass the local resolver: Firefox turns DNS over
+/// HTTPS off and Apple devices leave iCloud Private Relay alone if these do not resolve.
+const CANARY_DOMAINS: &[&str] = &[
+    "use-application-dns.net",
+    "mask.icloud.com",
+    "mask-h2.icloud.com",
+];
+
+/// Public DNS over HTTPS resolvers, see `data/doh_resolvers.txt`. Kept along with their (one
+/// based) line.
+static DOH_RESOLVERS: LazyLock<Vec<(usize, String)>> = LazyLock::new(|| {
+    include_str!("../../data/doh_resolvers.txt")
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim_start().starts_with('#'))
+        .filter_map(|(idx, line)| normalize_domain(line).map(|domain| (idx + 1, domain)))
+        .collect()
+});
+
+/// Built-in policies keeping clients from bypassing rustle by way of encrypted DNS.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DohPolicy {
+    /// Answer the canary domains with NXDOMAIN.
+    pub canary_domains: bool,
+    /// Block the hostnames of public DNS over HTTPS resolvers.
+    pub block_resolvers: bool,
+}
+
+impl DohPolicy {
+    /// The position (one based) and entry of the canary domain, if the domain is one.
+    pub fn canary(&self, domain: &str) -> Option<(usize, &'static str)> {
+        if !self.canary_domains {
+            return None;
+        }
+        CANARY_DOMAINS
+            .iter()
+            .enumerate()
+            .find(|(_, canary)| domain == **canary)
+            .map(|(idx, canary)| (idx + 1, *canary))
+    }
+
+    /// The line and entry of the resolver list covering the domain, if any.
+    pub fn resolver(&self, domain: &str) -> Option<(usize, &'static str)> {
+        if !self.block_resolvers {
+            return None;
+        }
+        DOH_RESOLVERS
+            .iter()
+            .find(|(_, resolver)| is_same_or_subdomain(domain, resolver))
+            .map(|(line, resolver)| (*line, resolver.as_str()))
+    }
+}
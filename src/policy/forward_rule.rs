@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::block_list::{is_same_or_subdomain, normalize_domain};
+
+/// Sends queries for the given domains (and their subdomains) to another upstream server.
+/// Written either as `{ "domains": ["corp"], "upstream": "10.0.0.1" }` or as a dnsmasq `server`
+/// line, i.e. `server=/corp/168.192.in-addr.arpa/10.0.0.1#53`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "ForwardRuleSpec")]
+pub struct ForwardRule {
+    pub domains: Vec<String>,
+    /// `None` sends the queries to the default upstream, which makes for exceptions to rules
+    /// on a parent domain.
+    pub upstream: Option<SocketAddr>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ForwardRuleSpec {
+    Dnsmasq(String),
+    Rule {
+        domains: Vec<String>,
+        upstream: Option<String>,
+    },
+}
+
+impl ForwardRule {
+    /// Parses a dnsmasq `server=/domain/.../address[#port]` line. An address of `#` stands for
+    /// the default upstream.
+    pub fn from_dnsmasq(line: &str) -> Result<Self, String> {
+        let invalid = || format!("{} is not a dnsmasq server line", line);
+        let spec = line.trim().strip_prefix("server=/").ok_or_else(invalid)?;
+        let (domains, upstream) = spec.rsplit_once('/').ok_or_else(invalid)?;
+        let upstream = match upstream {
+            "#" => None,
+            "" => return Err(format!("{}: local only domains are not supported", line)),
+            upstream => Some(match upstream.split_once('#') {
+                Some((ip, port)) => SocketAddr::new(
+                    ip.parse().map_err(|_| invalid())?,
+                    port.parse().map_err(|_| invalid())?,
+                ),
+                None => parse_upstream(upstream)?,
+            }),
+        };
+        Self::new(domains.split('/'), upstream)
+    }
+
+    fn new<'a>(
+        domains: impl Iterator<Item = &'a str>,
+        upstream: Option<SocketAddr>,
+    ) -> Result<Self, String> {
+        let domains = domains
+            .map(|domain| normalize_domain(domain).ok_or(format!("{} is not a domain", domain)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ForwardRule { domains, upstream })
+    }
+
+    /// The longest of the rule's domains covering the given one.
+    pub fn matches<'a>(&'a self, domain: &str) -> Option<&'a str> {
+        self.domains
+            .iter()
+            .filter(|parent| is_same_or_subdomain(domain, parent))
+            .max_by_key(|parent| parent.len())
+            .map(String::as_str)
+    }
+}
+
+impl TryFrom<ForwardRuleSpec> for ForwardRule {
+    type Error = String;
+
+    fn try_from(spec: ForwardRuleSpec) -> Result<Self, Self::Error> {
+        match spec {
+            ForwardRuleSpec::Dnsmasq(line) => ForwardRule::from_dnsmasq(&line),
+            ForwardRuleSpec::Rule { domains, upstream } => ForwardRule::new(
+                domains.iter().map(String::as_str),
+                upstream.as_deref().map(parse_upstream).transpose()?,
+            ),
+        }
+    }
+}
+
+/// Finds the rule for a domain. The most specific rule wins, regardless of the order.
+pub fn forward_rule_for<'a>(rules: &'a [ForwardRule], domain: &str) -> Option<&'a ForwardRule> {
+    rules
+        .iter()
+        .filter_map(|rule| rule.matches(domain).map(|parent| (parent.len(), rule)))
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, rule)| rule)
+}
+
+/// An address with an optional port, 53 if left out.
+fn parse_upstream(s: &str) -> Result<SocketAddr, String> {
+    s.parse::<SocketAddr>()
+        .or_else(|_| s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("{} is not an upstream address", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dnsmasq_lines() {
+        let rule = ForwardRule::from_dnsmasq("server=/a/b/1.2.3.4#5353").unwrap();
+        assert_eq!(rule.domains, ["a", "b"]);
+        assert_eq!(rule.upstream, Some("1.2.3.4:5353".parse().unwrap()));
+
+        let rule = ForwardRule::from_dnsmasq("server=/Corp./10.0.0.1").unwrap();
+        assert_eq!(rule.domains, ["corp"]);
+        assert_eq!(rule.upstream, Some("10.0.0.1:53".parse().unwrap()));
+
+        let rule = ForwardRule::from_dnsmasq("server=/corp/fd00::1#5353").unwrap();
+        assert_eq!(rule.upstream, Some("[fd00::1]:5353".parse().unwrap()));
+    }
+
+    #[test]
+    fn hash_stands_for_the_default_upstream() {
+        let rule = ForwardRule::from_dnsmasq("server=/a/#").unwrap();
+        assert_eq!(rule.domains, ["a"]);
+        assert_eq!(rule.upstream, None);
+    }
+
+    #[test]
+    fn refuses_malformed_lines() {
+        for line in [
+            "",
+            "server=1.2.3.4",
+            "address=/a/1.2.3.4",
+            "server=/a",
+            "server=/a/",
+            "server=//1.2.3.4",
+            "server=/a//1.2.3.4",
+            "server=/a b/1.2.3.4",
+            "server=/a/1.2.3",
+            "server=/a/1.2.3.4#",
+            "server=/a/1.2.3.4#dns",
+            "server=/a/1.2.3.4#65536",
+        ] {
+            assert!(ForwardRule::from_dnsmasq(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn the_most_specific_rule_wins() {
+        let rules = [
+            ForwardRule::from_dnsmasq("server=/corp/10.0.0.1").unwrap(),
+            ForwardRule::from_dnsmasq("server=/public.corp/#").unwrap(),
+        ];
+        let upstream = |domain| forward_rule_for(&rules, domain).map(|rule| rule.upstream);
+        assert_eq!(upstream("intranet.corp"), Some(rules[0].upstream));
+        assert_eq!(upstream("www.public.corp"), Some(None));
+        assert_eq!(upstream("notcorp"), None);
+    }
+}
@@ -0,0 +1,16 @@
+mod arp_table;
+mod client_group;
+mod doh;
+mod forward_rule;
+mod pause;
+mod qtype_rule;
+mod safe_search;
+mod schedule;
+
+pub use client_group::*;
+pub use doh::*;
+pub use forward_rule::*;
+pub use pause::*;
+pub use qtype_rule::*;
+pub use safe_search::*;
+pub use schedule::*;
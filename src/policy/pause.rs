@@ -0,0 +1,170 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Pauses longer than a week are refused, blocking is better turned off in the config by then.
+pub const MAX_PAUSE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Blocking switched off for a while, for every client or for the clients of a single group.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pause {
+    /// `None` for a pause covering every client.
+    pub group: Option<String>,
+    pub until: DateTime<Local>,
+}
+
+impl std::fmt::Display for Pause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.group {
+            Some(group) => write!(f, "Blocking is paused for group {}", group)?,
+            None => write!(f, "Blocking is paused")?,
+        }
+        write!(f, " until {}", self.until.format("%H:%M:%S"))
+    }
+}
+
+/// The pauses in effect, keyed by group. Pauses end on their own: each one is cleared by a timer
+/// once it runs out.
+#[derive(Debug, Default)]
+pub struct Pauses {
+    until: Arc<RwLock<HashMap<Option<String>, DateTime<Local>>>>,
+}
+
+impl Pauses {
+    /// Pauses blocking from now on, replacing any earlier pause of the same group.
+    /// Fails for pauses longer than `MAX_PAUSE`.
+    pub async fn pause(&self, group: Option<String>, duration: Duration) -> Result<Pause, String> {
+        let until = Some(duration)
+            .filter(|duration| *duration <= MAX_PAUSE)
+            .and_then(|duration| chrono::Duration::from_std(duration).ok())
+            .and_then(|duration| Local::now().checked_add_signed(duration))
+            .ok_or("Pause is too long")?;
+        self.until.write().await.insert(group.clone(), until);
+
+        let pauses = self.until.clone();
+        let expiring = group.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            let mut pauses = pauses.write().await;
+            // The pause may have been ended early or replaced by another one since.
+            if pauses.get(&expiring) == Some(&until) {
+                pauses.remove(&expiring);
+                // TODO: log this
+                match expiring {
+                    Some(group) => println!("Blocking resumed for group {}", group),
+                    None => println!("Blocking resumed"),
+                }
+            }
+        });
+        Ok(Pause { group, until })
+    }
+
+    /// Returns false if the group was not paused.
+    pub async fn resume(&self, group: Option<&str>) -> bool {
+        self.until
+            .write()
+            .await
+            .remove(&group.map(String::from))
+            .is_some()
+    }
+
+    pub async fn active(&self) -> Vec<Pause> {
+        let now = Local::now();
+        self.until
+            .read()
+            .await
+            .iter()
+            .filter(|(_, until)| **until > now)
+            .map(|(group, until)| Pause {
+                group: group.clone(),
+                until: *until,
+            })
+            .collect()
+    }
+
+    /// Until when blocking is paused for the clients of the given group (or of no group). A
+    /// pause covering every client counts too.
+    pub async fn paused_until(&self, group: Option<&str>) -> Option<DateTime<Local>> {
+        let now = Local::now();
+        let pauses = self.until.read().await;
+        [None, group.map(String::from)]
+            .iter()
+            .filter_map(|group| pauses.get(group))
+            .filter(|until| **until > now)
+            .max()
+            .copied()
+    }
+}
+
+/// Parses durations like `90`, `90s`, `5m` or `1h`. Plain numbers are seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("{} is not a duration", s))?;
+    let secs = match unit {
+        "s" => Some(number),
+        "m" => number.checked_mul(60),
+        "h" => number.checked_mul(60 * 60),
+        _ => return Err(format!("{} is not a duration, use s, m or h", s)),
+    };
+    secs.map(Duration::from_secs)
+        .ok_or(format!("{} is too long", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pauses_end_on_their_own() {
+        let pauses = Pauses::default();
+        pauses.pause(None, Duration::from_millis(50)).await.unwrap();
+        pauses
+            .pause(Some("kids".to_string()), Duration::from_millis(50))
+            .await
+            .unwrap();
+        // Replacing a pause also replaces when it ends.
+        pauses
+            .pause(Some("kids".to_string()), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(pauses.paused_until(None).await.is_some());
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(pauses.until.read().await.get(&None).is_none());
+        assert!(pauses.paused_until(None).await.is_none());
+        let active = pauses.active().await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].group.as_deref(), Some("kids"));
+        assert!(pauses.resume(Some("kids")).await);
+        assert!(!pauses.resume(Some("kids")).await);
+    }
+
+    #[tokio::test]
+    async fn refuses_pauses_longer_than_a_week() {
+        let pauses = Pauses::default();
+        assert!(pauses.pause(None, MAX_PAUSE).await.is_ok());
+        assert!(pauses.pause(None, MAX_PAUSE * 2).await.is_err());
+        assert!(pauses.pause(None, Duration::MAX).await.is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(5 * 60)));
+        assert_eq!(parse_duration(" 1h "), Ok(Duration::from_secs(60 * 60)));
+        for s in ["", "h", "5d", "1.5h", "-5m", "99999999999999999999h"] {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+        assert!(parse_duration("18446744073709551615h").is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::Utc;
+use serde::Deserialize;
+
+use super::schedule::Schedule;
+use crate::block_list::is_same_or_subdomain;
+use crate::query_service::{BlockingMode, QType};
+
+/// What happens to a query caught by a `QTypeRule`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QTypeAction {
+    NoData,
+    Refused,
+    NxDomain,
+    /// Do not answer at all.
+    Drop,
+}
+
+impl QTypeAction {
+    /// How to answer, `None` meaning no answer is sent.
+    pub fn blocking_mode(&self) -> Option<BlockingMode> {
+        match self {
+            QTypeAction::NoData => Some(BlockingMode::NoData),
+            QTypeAction::Refused => Some(BlockingMode::Refused),
+            QTypeAction::NxDomain => Some(BlockingMode::NxDomain),
+            QTypeAction::Drop => None,
+        }
+    }
+}
+
+/// Blocks queries by record type, either for every domain or only for the given one (and its
+/// subdomains). For example:
+/// - `{ "q_type": "HTTPS", "action": "nodata" }` keeps clients from learning ECH configs.
+/// - `{ "q_type": "ANY", "action": "refused" }` refuses ANY queries.
+/// - `{ "q_type": 28, "domain": "example.com", "action": "nodata" }` strips AAAA records.
+#[derive(Clone, Debug, Deserialize)]
+pub struct QTypeRule {
+    pub q_type: QType,
+    #[serde(default)]
+    pub domain: Option<String>,
+    pub action: QTypeAction,
+    #[serde(default)]
+    pub schedule: Schedule,
+}
+
+impl QTypeRule {
+    pub fn matches(&self, q_type: QType, domain: &str) -> bool {
+        self.q_type == q_type
+            && self.schedule.is_active(Utc::now())
+            && match &self.domain {
+                Some(parent) => is_same_or_subdomain(domain, parent),
+                None => true,
+            }
+    }
+}
+
+impl std::fmt::Display for QTypeRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.q_type)?;
+        if let Some(domain) = &self.domain {
+            write!(f, " {}", domain)?;
+        }
+        write!(f, " -> {:?}", self.action)
+    }
+}
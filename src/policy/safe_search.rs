@@ -0,0 +1,36 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::local_zone::{LocalRecord, DEFAULT_TTL};
+use crate::query_service::RData;
+
+/// The search engines and video sites we know how to force into safe search, keyed by name.
+/// Each provider runs an endpoint that only ever serves safe results, so their domains are
+/// rewritten to it.
+static PROVIDERS: LazyLock<HashMap<String, SafeSearchProvider>> = LazyLock::new(|| {
+    serde_json::from_str(include_str!("../../data/safe_search.json"))
+        .expect("data/safe_search.json is malformed")
+});
+
+/// The domains of every provider as aliases to its safe endpoint, served as local records to
+/// clients with safe search on. Only the exact domains of a provider are aliased, other
+/// subdomains (i.e. `mail.google.com`) are left alone.
+pub static SAFE_SEARCH_RECORDS: LazyLock<Vec<LocalRecord>> = LazyLock::new(|| {
+    PROVIDERS
+        .values()
+        .flat_map(|provider| {
+            provider.domains.iter().map(|domain| LocalRecord {
+                name: domain.clone(),
+                ttl: DEFAULT_TTL,
+                data: RData::CNAME(provider.target.clone()),
+            })
+        })
+        .collect()
+});
+
+#[derive(Debug, Deserialize)]
+struct SafeSearchProvider {
+    target: String,
+    domains: Vec<String>,
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// When a rule, list or group is in effect: during any of its windows, or always if it has none.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Schedule(Vec<TimeWindow>);
+
+/// A weekly time window, i.e. `{ "days": ["mon", "fri"], "from": "09:00", "to": "17:00" }`.
+/// - `days` defaults to every day.
+/// - A window ending before it starts runs past midnight (`21:00` to `07:00`), the part after
+///   midnight belonging to the day it started. `from` and `to` default to midnight, so leaving
+///   both out covers the whole day.
+/// - Times are in the local timezone unless a `timezone` (an IANA name like `Europe/Berlin`) is
+///   given. Either way they follow daylight saving time.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeWindow {
+    pub days: Vec<Weekday>,
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
+}
+
+impl Schedule {
+    pub fn is_always(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.is_always() || self.0.iter().any(|window| window.contains(now))
+    }
+}
+
+impl TimeWindow {
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let (day, time) = match self.timezone {
+            Some(timezone) => {
+                let now = now.with_timezone(&timezone);
+                (now.weekday(), now.time())
+            }
+            None => {
+                let now = now.with_timezone(&Local);
+                (now.weekday(), now.time())
+            }
+        };
+        let on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.from < self.to {
+            on(day) && self.from <= time && time < self.to
+        } else {
+            (on(day) && self.from <= time) || (on(day.pred()) && time < self.to)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn window(days: &[Weekday], from: &str, to: &str) -> TimeWindow {
+        TimeWindow {
+            days: days.to_vec(),
+            from: from.parse().unwrap(),
+            to: to.parse().unwrap(),
+            timezone: Some(Tz::Europe__Berlin),
+        }
+    }
+
+    /// The instant it is at the given local time in Berlin.
+    fn berlin(year: i32, month: u32, day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        Tz::Europe__Berlin
+            .with_ymd_and_hms(year, month, day, hour, min, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn contains_times_between_from_and_to() {
+        // 2026-10-19 is a Monday.
+        let office = window(&[Weekday::Mon], "09:00:00", "17:00:00");
+        assert!(office.contains(berlin(2026, 10, 19, 9, 0)));
+        assert!(office.contains(berlin(2026, 10, 19, 16, 59)));
+        assert!(!office.contains(berlin(2026, 10, 19, 17, 0)));
+        assert!(!office.contains(berlin(2026, 10, 19, 8, 59)));
+        assert!(!office.contains(berlin(2026, 10, 20, 10, 0)));
+    }
+
+    #[test]
+    fn windows_ending_before_they_start_run_past_midnight() {
+        let night = window(&[Weekday::Fri], "21:00:00", "07:00:00");
+        // 2026-10-23 is a Friday.
+        assert!(night.contains(berlin(2026, 10, 23, 22, 0)));
+        assert!(night.contains(berlin(2026, 10, 24, 6, 59)));
+        assert!(!night.contains(berlin(2026, 10, 24, 7, 0)));
+        assert!(!night.contains(berlin(2026, 10, 24, 22, 0)));
+        // The early hours of Friday belong to Thursday's window.
+        assert!(!night.contains(berlin(2026, 10, 23, 6, 0)));
+    }
+
+    #[test]
+    fn empty_windows_cover_every_day() {
+        let always = window(&[], "00:00:00", "00:00:00");
+        assert!(always.contains(berlin(2026, 10, 19, 0, 0)));
+        assert!(always.contains(berlin(2026, 10, 25, 23, 59)));
+    }
+
+    #[test]
+    fn follows_daylight_saving_time() {
+        let office = window(&[], "09:00:00", "17:00:00");
+        // 09:30 in Berlin is 07:30 UTC in summer and 08:30 UTC in winter.
+        let summer = Utc.with_ymd_and_hms(2026, 7, 1, 7, 30, 0).unwrap();
+        let winter = Utc.with_ymd_and_hms(2026, 12, 1, 7, 30, 0).unwrap();
+        assert!(office.contains(summer));
+        assert!(!office.contains(winter));
+    }
+
+    #[test]
+    fn timezones_are_iana_names() {
+        let window: TimeWindow =
+            serde_json::from_str(r#"{ "from": "09:00", "timezone": "America/New_York" }"#).unwrap();
+        assert_eq!(window.timezone, Some(Tz::America__New_York));
+        assert!(serde_json::from_str::<TimeWindow>(r#"{ "timezone": "+02:00" }"#).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use super::dns_query_answer::*;
+use super::dns_query_question::DNSQueryQuestion;
+use super::dns_upstream_response::UpstreamRecord;
+use super::q_type::QType;
+
+/// Blocked answers are not meant to stick around in client caches.
+const BLOCKED_TTL: u32 = 10;
+
+/// How a blocked query gets answered. Written as `null`, `nxdomain`, `nodata`, `refused` or an ip
+/// address.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum BlockingMode {
+    /// Answer with the unspecified address (`0.0.0.0` or `::`). Clients fail right away when
+    /// connecting to it. Queries for other record types get an empty answer.
+    #[default]
+    Null,
+    /// Pretend the domain does not exist.
+    NxDomain,
+    /// Pretend the domain exists but has no records of the queried type.
+    NoData,
+    /// Refuse to answer.
+    Refused,
+    /// Answer with the address of the block page server, which tells users what was blocked and
+    /// why. Queries for the other address family get an empty answer.
+    Ip(IpAddr),
+}
+
+impl FromStr for BlockingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(BlockingMode::Null),
+            "nxdomain" => Ok(BlockingMode::NxDomain),
+            "nodata" => Ok(BlockingMode::NoData),
+            "refused" => Ok(BlockingMode::Refused),
+            _ => s
+                .parse()
+                .map(BlockingMode::Ip)
+                .map_err(|_| format!("Unknown blocking mode {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for BlockingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockingMode::Null => write!(f, "null"),
+            BlockingMode::NxDomain => write!(f, "nxdomain"),
+            BlockingMode::NoData => write!(f, "nodata"),
+            BlockingMode::Refused => write!(f, "refused"),
+            BlockingMode::Ip(ip) => write!(f, "{}", ip),
+        }
+    }
+}
+
+impl TryFrom<String> for BlockingMode {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<BlockingMode> for String {
+    fn from(blocking_mode: BlockingMode) -> Self {
+        blocking_mode.to_string()
+    }
+}
+
+impl BlockingMode {
+    /// Encodes the answer to a blocked query.
+    pub fn answer(&self, query: &DNSQueryQuestion) -> Vec<u8> {
+        let name = query.q_name_array.join(".");
+        let answer = match self {
+            BlockingMode::Null | BlockingMode::Ip(_) => {
+                let (v4, v6) = match self {
+                    BlockingMode::Ip(IpAddr::V4(ip)) => (Some(*ip), None),
+                    BlockingMode::Ip(IpAddr::V6(ip)) => (None, Some(*ip)),
+                    _ => (Some(Ipv4Addr::UNSPECIFIED), Some(Ipv6Addr::UNSPECIFIED)),
+                };
+                let data = match query.q_type {
+                    QType::A => v4.map(RData::A),
+                    QType::AAAA => v6.map(RData::AAAA),
+                    _ => None,
+                };
+                let answers = data
+                    .map(|data| ResourceRecord {
+                        name,
+                        ttl: BLOCKED_TTL,
+                        data,
+                    })
+                    .into_iter()
+                    .collect();
+                DNSQueryAnswer::reply_to(query, R_CODE_NO_ERROR, answers)
+            }
+            BlockingMode::NxDomain => DNSQueryAnswer::reply_to(query, R_CODE_NX_DOMAIN, vec![]),
+            BlockingMode::NoData => DNSQueryAnswer::reply_to(query, R_CODE_NO_ERROR, vec![]),
+            BlockingMode::Refused => DNSQueryAnswer::reply_to(query, R_CODE_REFUSED, vec![]),
+        };
+        answer.into()
+    }
+
+    /// Blocks the given A and AAAA records of an upstream response.
+    /// With `Null` only the matching records are replaced (in place, since the unspecified
+    /// address is just as long as any other), everything else in the response stays intact. The
+    /// other modes can not apply to part of a response, so they replace it as a whole.
+    pub fn patch_answers(
+        &self,
+        query: &DNSQueryQuestion,
+        response_bytes: &[u8],
+        records: &[&UpstreamRecord],
+    ) -> Vec<u8> {
+        match self {
+            BlockingMode::Null => {
+                let mut patched = response_bytes.to_vec();
+                for record in records {
+                    let data: &[u8] = match record.data {
+                        Some(RData::A(_)) => &Ipv4Addr::UNSPECIFIED.octets(),
+                        Some(RData::AAAA(_)) => &Ipv6Addr::UNSPECIFIED.octets(),
+                        _ => continue,
+                    };
+                    patched[record.data_range.clone()].copy_from_slice(data);
+                    patched[record.ttl_range()].copy_from_slice(&BLOCKED_TTL.to_be_bytes());
+                }
+                patched
+            }
+            _ => self.answer(query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_service::dns_upstream_response::DNSUpstreamResponse;
+    use crate::query_service::q_class::QClass;
+
+    fn query() -> DNSQueryQuestion<'static> {
+        DNSQueryQuestion {
+            message_id: 0x1234,
+            q_name_array: vec!["www", "example"],
+            q_type: QType::A,
+            q_class: QClass::IN,
+            ..Default::default()
+        }
+    }
+
+    /// An upstream answer aliasing www.example to a tracker, which resolves to an address.
+    fn cloaked_response() -> Vec<u8> {
+        let record = |name: &str, data| ResourceRecord {
+            name: name.to_string(),
+            ttl: 300,
+            data,
+        };
+        let answers = vec![
+            record("www.example", RData::CNAME("x.tracker.example".to_string())),
+            record("x.tracker.example", RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+        ];
+        DNSQueryAnswer::reply_to(&query(), R_CODE_NO_ERROR, answers).into()
+    }
+
+    #[test]
+    fn null_blocks_the_addresses_a_chain_ends_in() {
+        let bytes = cloaked_response();
+        let response = DNSUpstreamResponse::try_from(&bytes).unwrap();
+        let blocked: Vec<_> = response
+            .answers
+            .iter()
+            .filter(|record| record.name == "x.tracker.example")
+            .collect();
+
+        let patched = BlockingMode::Null.patch_answers(&query(), &bytes, &blocked);
+        assert_eq!(patched.len(), bytes.len());
+        let patched = DNSUpstreamResponse::try_from(&patched).unwrap();
+        assert_eq!(
+            patched.answers[0].data,
+            Some(RData::CNAME("x.tracker.example".to_string()))
+        );
+        assert_eq!(patched.answers[0].ttl, 300);
+        assert_eq!(
+            patched.answers[1].data,
+            Some(RData::A(Ipv4Addr::UNSPECIFIED))
+        );
+        assert_eq!(patched.answers[1].ttl, BLOCKED_TTL);
+    }
+
+    #[test]
+    fn other_modes_replace_the_whole_response() {
+        let bytes = cloaked_response();
+        let response = DNSUpstreamResponse::try_from(&bytes).unwrap();
+        let blocked: Vec<_> = response.answers.iter().skip(1).collect();
+
+        let patched = BlockingMode::NxDomain.patch_answers(&query(), &bytes, &blocked);
+        let patched = DNSUpstreamResponse::try_from(&patched).unwrap();
+        assert_eq!(patched.r_code, R_CODE_NX_DOMAIN);
+        assert!(patched.answers.is_empty());
+
+        let block_page = BlockingMode::Ip("192.0.2.53".parse().unwrap());
+        let patched = block_page.patch_answers(&query(), &bytes, &blocked);
+        let patched = DNSUpstreamResponse::try_from(&patched).unwrap();
+        assert_eq!(patched.answers.len(), 1);
+        assert_eq!(patched.answers[0].name, "www.example");
+        assert_eq!(
+            patched.answers[0].data,
+            Some(RData::A(Ipv4Addr::new(192, 0, 2, 53)))
+        );
+    }
+}
@@ -1,4 +1,81 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::dns_query_question::DNSQueryQuestion;
+use super::{q_class::QClass, q_type::QType};
+
+pub const R_CODE_NO_ERROR: u8 = 0;
+pub const R_CODE_NX_DOMAIN: u8 = 3;
+pub const R_CODE_REFUSED: u8 = 5;
+
+/// The data section of a resource record.
+/// Only the record types rustle answers with on its own are covered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RData {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    CNAME(String),
+    PTR(String),
+    /// Split up into strings of at most 255 bytes on the wire.
+    TXT(String),
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+}
+
+impl RData {
+    pub fn q_type(&self) -> QType {
+        match self {
+            RData::A(_) => QType::A,
+            RData::AAAA(_) => QType::AAAA,
+            RData::CNAME(_) => QType::CNAME,
+            RData::PTR(_) => QType::PTR,
+            RData::TXT(_) => QType::TXT,
+            RData::SRV { .. } => QType::SRV,
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            RData::A(ip) => buf.extend_from_slice(&ip.octets()),
+            RData::AAAA(ip) => buf.extend_from_slice(&ip.octets()),
+            RData::CNAME(name) | RData::PTR(name) => encode_name(buf, name),
+            RData::TXT(text) => {
+                if text.is_empty() {
+                    buf.push(0);
+                }
+                for chunk in text.as_bytes().chunks(255) {
+                    buf.push(chunk.len() as u8);
+                    buf.extend_from_slice(chunk);
+                }
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                buf.extend_from_slice(&priority.to_be_bytes());
+                buf.extend_from_slice(&weight.to_be_bytes());
+                buf.extend_from_slice(&port.to_be_bytes());
+                encode_name(buf, target);
+            }
+        }
+    }
+}
+
+/// A resource record of the answer section. The class is always `IN`.
+#[derive(Clone, Debug)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub data: RData,
+}
+
 #[derive(derive_builder::Builder, Default, Debug)]
+#[builder(default)]
 pub struct DNSQueryAnswer<'a> {
     message_id: u16,
     op_code: u8,
@@ -9,16 +86,198 @@ pub struct DNSQueryAnswer<'a> {
     is_answer_authenticated: bool,
     is_non_auth_answer_acceptable: bool,
     r_code: u8,
-    num_of_questions: u16,
-    num_of_answers: u16,
-    num_of_authorities: u16,
-    num_of_additional_rrs: u16,
     q_name_array: Vec<&'a str>,
-    ans_array: Vec<&'a str>,
+    q_type: QType,
+    q_class: QClass,
+    answers: Vec<ResourceRecord>,
+}
+
+impl<'a> DNSQueryAnswer<'a> {
+    /// An answer to the given query, echoing its question section.
+    pub fn reply_to(
+        query: &DNSQueryQuestion<'a>,
+        r_code: u8,
+        answers: Vec<ResourceRecord>,
+    ) -> Self {
+        DNSQueryAnswer {
+            message_id: query.message_id,
+            op_code: query.op_code,
+            is_recursion_desired: query.is_recursive,
+            is_recursion_available: true,
+            r_code,
+            q_name_array: query.q_name_array.clone(),
+            q_type: query.q_type,
+            q_class: query.q_class,
+            answers,
+            ..Default::default()
+        }
+    }
+
+    /// Marks the answer as coming from the server in charge of the name, as local data does.
+    pub fn authoritative(self) -> Self {
+        DNSQueryAnswer {
+            is_authoritative: true,
+            ..self
+        }
+    }
+}
+
+impl<'a> From<DNSQueryAnswer<'a>> for Vec<u8> {
+    /// Encodes the answer in wire format, see `DNSQueryQuestion::try_from` for the layout of the
+    /// header. Names are written out in full, no compression is used.
+    fn from(answer: DNSQueryAnswer<'a>) -> Self {
+        let mut buf = Vec::with_capacity(512);
+        buf.extend_from_slice(&answer.message_id.to_be_bytes());
+        buf.push(
+            0b1000_0000
+                | (answer.op_code & 0b0000_1111) << 3
+                | (answer.is_authoritative as u8) << 2
+                | (answer.is_truncated as u8) << 1
+                | answer.is_recursion_desired as u8,
+        );
+        buf.push(
+            (answer.is_recursion_available as u8) << 7
+                | (answer.is_answer_authenticated as u8) << 5
+                | (answer.is_non_auth_answer_acceptable as u8) << 4
+                | answer.r_code & 0b0000_1111,
+        );
+        buf.extend_from_slice(&1u16.to_be_bytes());
+        buf.extend_from_slice(&(answer.answers.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&0u16.to_be_bytes());
+        buf.extend_from_slice(&0u16.to_be_bytes());
+
+        for label in &answer.q_name_array {
+            encode_label(&mut buf, label);
+        }
+        buf.push(0);
+        buf.extend_from_slice(&answer.q_type.to_u16().to_be_bytes());
+        buf.extend_from_slice(&answer.q_class.to_u16().to_be_bytes());
+
+        for record in &answer.answers {
+            encode_name(&mut buf, &record.name);
+            buf.extend_from_slice(&record.data.q_type().to_u16().to_be_bytes());
+            buf.extend_from_slice(&QClass::IN.to_u16().to_be_bytes());
+            buf.extend_from_slice(&record.ttl.to_be_bytes());
+            let mut data = Vec::new();
+            record.data.encode(&mut data);
+            buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            buf.extend_from_slice(&data);
+        }
+
+        buf
+    }
+}
+
+pub(super) fn encode_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        encode_label(buf, label);
+    }
+    buf.push(0);
 }
 
-impl<'a> Into<Vec<u8>> for DNSQueryAnswer<'a> {
-    fn into(self) -> Vec<u8> {
-        Vec::new()
+fn encode_label(buf: &mut Vec<u8>, label: &str) {
+    // Labels are capped at 63 bytes by the protocol.
+    let label = &label.as_bytes()[..label.len().min(63)];
+    buf.push(label.len() as u8);
+    buf.extend_from_slice(label);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_service::dns_upstream_response::DNSUpstreamResponse;
+
+    fn query() -> DNSQueryQuestion<'static> {
+        DNSQueryQuestion {
+            message_id: 0x1234,
+            is_recursive: true,
+            q_name_array: vec!["www", "example"],
+            q_type: QType::A,
+            q_class: QClass::IN,
+            ..Default::default()
+        }
+    }
+
+    fn record(name: &str, data: RData) -> ResourceRecord {
+        ResourceRecord {
+            name: name.to_string(),
+            ttl: 300,
+            data,
+        }
+    }
+
+    #[test]
+    fn encodes_the_header_and_question() {
+        let bytes: Vec<u8> = DNSQueryAnswer::reply_to(&query(), R_CODE_NX_DOMAIN, vec![]).into();
+        assert_eq!(
+            bytes,
+            b"\x12\x34\x81\x83\x00\x01\x00\x00\x00\x00\x00\x00\x03www\x07example\x00\x00\x01\x00\x01"
+        );
+
+        let bytes: Vec<u8> = DNSQueryAnswer::reply_to(&query(), R_CODE_NO_ERROR, vec![])
+            .authoritative()
+            .into();
+        assert_eq!(bytes[2], 0x85);
+    }
+
+    #[test]
+    fn encodes_records() {
+        let answers = vec![
+            record("www.example", RData::CNAME("cdn.example.".to_string())),
+            record("cdn.example", RData::A(Ipv4Addr::new(192, 0, 2, 1))),
+            record("cdn.example", RData::AAAA(Ipv6Addr::LOCALHOST)),
+        ];
+        let bytes: Vec<u8> = DNSQueryAnswer::reply_to(&query(), R_CODE_NO_ERROR, answers).into();
+
+        let response = DNSUpstreamResponse::try_from(&bytes).unwrap();
+        assert_eq!(response.message_id, 0x1234);
+        assert_eq!(response.r_code, R_CODE_NO_ERROR);
+        let decoded: Vec<_> = response
+            .answers
+            .iter()
+            .map(|record| (record.name.as_str(), record.ttl, record.data.clone()))
+            .collect();
+        assert_eq!(
+            decoded,
+            [
+                (
+                    "www.example",
+                    300,
+                    Some(RData::CNAME("cdn.example".to_string()))
+                ),
+                (
+                    "cdn.example",
+                    300,
+                    Some(RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+                ),
+                ("cdn.example", 300, Some(RData::AAAA(Ipv6Addr::LOCALHOST))),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_long_texts() {
+        let mut data = Vec::new();
+        RData::TXT("x".repeat(300)).encode(&mut data);
+        assert_eq!(data.len(), 302);
+        assert_eq!(data[0], 255);
+        assert_eq!(data[256], 45);
+
+        let mut data = Vec::new();
+        RData::TXT(String::new()).encode(&mut data);
+        assert_eq!(data, [0]);
+    }
+
+    #[test]
+    fn encodes_srv_records() {
+        let mut data = Vec::new();
+        RData::SRV {
+            priority: 1,
+            weight: 2,
+            port: 5353,
+            target: "nas.home".to_string(),
+        }
+        .encode(&mut data);
+        assert_eq!(data, b"\x00\x01\x00\x02\x14\xe9\x03nas\x04home\x00");
     }
 }
@@ -1,5 +1,6 @@
 use std::str::from_utf8;
 
+use super::dns_query_answer::encode_name;
 use super::{q_class::QClass, q_type::QType};
 
 #[derive(derive_builder::Builder, Default, Debug)]
@@ -18,6 +19,27 @@ pub struct DNSQueryQuestion<'a> {
     pub q_class: QClass,
 }
 
+impl DNSQueryQuestion<'_> {
+    /// Encodes the same query, asking about another name instead. Additional records (i.e. EDNS
+    /// options) are left out.
+    pub fn renamed(&self, name: &str) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(512);
+        buf.extend_from_slice(&self.message_id.to_be_bytes());
+        buf.push(
+            (self.op_code & 0b0000_1111) << 3
+                | (self.is_truncated as u8) << 1
+                | self.is_recursive as u8,
+        );
+        buf.push(0);
+        buf.extend_from_slice(&1u16.to_be_bytes());
+        buf.extend_from_slice(&[0; 6]);
+        encode_name(&mut buf, name);
+        buf.extend_from_slice(&self.q_type.to_u16().to_be_bytes());
+        buf.extend_from_slice(&self.q_class.to_u16().to_be_bytes());
+        buf
+    }
+}
+
 impl<'a> TryFrom<&'a Vec<u8>> for DNSQueryQuestion<'a> {
     type Error = Box<dyn std::error::Error + Send + Sync>;
 
@@ -38,12 +60,12 @@ impl<'a> TryFrom<&'a Vec<u8>> for DNSQueryQuestion<'a> {
     ///     - This is not a part of the question. This is where the answer goes.
     /// - `Number of Authority Resource Records`: u16.
     ///     - This is the number of authority resource records that are capable of providing definitive
-    ///     answers to the query questions.
+    ///       answers to the query questions.
     ///     - This is also not a part of the question.
     /// - `Number of Additional Resource Records`: u16.
     ///     - This is the number of additional resource records associated with the question.
     ///     - This is typically things that supplement the question (like querying for MX record, A
-    ///     record etc).
+    ///       record etc).
     ///     - Some examples are:
     ///         - A or AAAA records.
     ///         - OPT records.
@@ -53,9 +75,9 @@ impl<'a> TryFrom<&'a Vec<u8>> for DNSQueryQuestion<'a> {
     ///     - A variable-length field that contains the domain being queried.
     ///     - It's encoded as a series of labels, each with a length byte followed by the label itself.
     ///     - Each label is a segment in the domain being queried about, without the dots (the dots is
-    ///     what delimits the question, like www.google.com).
+    ///       what delimits the question, like www.google.com).
     /// - `Addition records`: Variable length. It has two possible record type. Both of which uses the
-    /// same format but depending on the record type the fields are repurposed.
+    ///   same format but depending on the record type the fields are repurposed.
     ///     - Normal case. The fields in this is very similar to the query section:
     ///         - `Domain Name`: Variable length. This shares the same format as the query section.
     ///         - `Type`: u16. This correlates to `QType`.
@@ -85,7 +107,7 @@ impl<'a> TryFrom<&'a Vec<u8>> for DNSQueryQuestion<'a> {
         // second two bytes should be flags
         // going to skip the second byte for now
         let _is_question = bytes[2] & 0b1000_0000 == 0;
-        let op_code = bytes[2] & 0b0111_1000 >> 3;
+        let op_code = (bytes[2] & 0b0111_1000) >> 3;
         let is_truncated = bytes[2] & 0b0000_0010 != 0;
         let is_recursive = bytes[2] & 0b0000_0001 != 0;
 
@@ -116,9 +138,9 @@ impl<'a> TryFrom<&'a Vec<u8>> for DNSQueryQuestion<'a> {
 
         // Additional records.
         // We are reading the first byte first to determine the record type.
-        let is_opt = {
-            let first_byte = bytes[cur_idx] as u8;
-            first_byte & 0b1111_1111 == 0b0000_0000
+        let is_opt = num_of_additional_rrs > 0 && {
+            let first_byte = bytes[cur_idx];
+            first_byte == 0b0000_0000
         };
 
         // Currently we are not doing anything with additional records.
@@ -140,8 +162,8 @@ impl<'a> TryFrom<&'a Vec<u8>> for DNSQueryQuestion<'a> {
                 packet_size
             };
 
-            let _r_code = (bytes[cur_idx] & 0b0000_1111) as u8;
-        } else {
+            let _r_code = bytes[cur_idx] & 0b0000_1111;
+        } else if num_of_additional_rrs > 0 {
             println!("Additional records is not OPT"); // TODO: Change this to logging later
         }
 
@@ -0,0 +1,268 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
+
+use super::dns_query_answer::RData;
+use super::q_type::QType;
+
+/// Pointers can only point backwards, so a well formed name never needs more jumps than this.
+const MAX_POINTER_JUMPS: usize = 64;
+
+/// A resource record of the answer section of an upstream response.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct UpstreamRecord {
+    pub name: String,
+    pub r_type: u16,
+    pub ttl: u32,
+    /// Where the data of the record sits in the response, so that it can be patched in place.
+    pub data_range: Range<usize>,
+    /// Only decoded for the record types we look into.
+    pub data: Option<RData>,
+}
+
+/// The parts of a response from the upstream server that are of interest to us.
+/// The header follows the same layout as described in `DNSQueryQuestion::try_from`. Unlike
+/// queries, responses make use of name compression: a label length with the two high bits set is
+/// instead a pointer to where the rest of the name continues in the message.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct DNSUpstreamResponse {
+    pub message_id: u16,
+    pub r_code: u8,
+    pub answers: Vec<UpstreamRecord>,
+}
+
+impl UpstreamRecord {
+    /// The ttl sits right before the data length, which sits right before the data.
+    pub fn ttl_range(&self) -> Range<usize> {
+        self.data_range.start - 6..self.data_range.start - 2
+    }
+}
+
+impl DNSUpstreamResponse {
+    /// Every name the answer chain aliases to, in order.
+    pub fn cname_targets(&self) -> impl Iterator<Item = &str> {
+        self.answers.iter().filter_map(|record| match &record.data {
+            Some(RData::CNAME(target)) => Some(target.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl TryFrom<&Vec<u8>> for DNSUpstreamResponse {
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn try_from(bytes: &Vec<u8>) -> Result<Self, Self::Error> {
+        if bytes.len() < 12 {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "bytes length is less than 12",
+            )));
+        }
+
+        let message_id = read_u16(bytes, 0)?;
+        let r_code = bytes[3] & 0b0000_1111;
+        let num_of_questions = read_u16(bytes, 4)?;
+        let num_of_answers = read_u16(bytes, 6)?;
+
+        let mut cur_idx = 12;
+        for _ in 0..num_of_questions {
+            let (_, next_idx) = read_name(bytes, cur_idx)?;
+            // q type and q class
+            cur_idx = next_idx + 4;
+        }
+
+        let mut answers = Vec::with_capacity(num_of_answers as usize);
+        for _ in 0..num_of_answers {
+            let (name, next_idx) = read_name(bytes, cur_idx)?;
+            cur_idx = next_idx;
+            let r_type = read_u16(bytes, cur_idx)?;
+            let ttl =
+                (read_u16(bytes, cur_idx + 4)? as u32) << 16 | read_u16(bytes, cur_idx + 6)? as u32;
+            let data_len = read_u16(bytes, cur_idx + 8)? as usize;
+            cur_idx += 10;
+            let data_range = cur_idx..cur_idx + data_len;
+            let data_bytes = bytes.get(data_range.clone()).ok_or(truncated())?;
+
+            let data = match QType::from_u16(r_type) {
+                Some(QType::A) if data_len == 4 => {
+                    let octets: [u8; 4] = data_bytes.try_into()?;
+                    Some(RData::A(Ipv4Addr::from(octets)))
+                }
+                Some(QType::AAAA) if data_len == 16 => {
+                    let octets: [u8; 16] = data_bytes.try_into()?;
+                    Some(RData::AAAA(Ipv6Addr::from(octets)))
+                }
+                Some(QType::CNAME) => Some(RData::CNAME(read_name(bytes, cur_idx)?.0)),
+                _ => None,
+            };
+
+            answers.push(UpstreamRecord {
+                name,
+                r_type,
+                ttl,
+                data_range,
+                data,
+            });
+            cur_idx += data_len;
+        }
+
+        Ok(DNSUpstreamResponse {
+            message_id,
+            r_code,
+            answers,
+        })
+    }
+}
+
+fn truncated() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, "response is truncated")
+}
+
+fn read_u16(bytes: &[u8], idx: usize) -> Result<u16, std::io::Error> {
+    match bytes.get(idx..idx + 2) {
+        Some(slice) => Ok((slice[0] as u16) << 8 | slice[1] as u16),
+        None => Err(truncated()),
+    }
+}
+
+/// Reads a (possibly compressed) name starting at `idx`.
+/// Returns the name, lower cased and dot separated, along with the index right after it.
+fn read_name(bytes: &[u8], idx: usize) -> Result<(String, usize), std::io::Error> {
+    let mut labels = Vec::new();
+    let mut cur_idx = idx;
+    // Where parsing resumes once the name is read, which is right after the first pointer.
+    let mut next_idx = None;
+    let mut jumps = 0;
+    loop {
+        let len = *bytes.get(cur_idx).ok_or(truncated())?;
+        if len & 0b1100_0000 == 0b1100_0000 {
+            let pointer = read_u16(bytes, cur_idx)? & 0b0011_1111_1111_1111;
+            next_idx.get_or_insert(cur_idx + 2);
+            jumps += 1;
+            if jumps > MAX_POINTER_JUMPS {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "too many compression pointers",
+                ));
+            }
+            cur_idx = pointer as usize;
+            continue;
+        }
+        if len == 0 {
+            cur_idx += 1;
+            break;
+        }
+        let label = bytes
+            .get(cur_idx + 1..cur_idx + 1 + len as usize)
+            .ok_or(truncated())?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        cur_idx += len as usize + 1;
+    }
+
+    Ok((labels.join("."), next_idx.unwrap_or(cur_idx)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response header with the given section counts, followed by the body.
+    fn response(questions: u16, answers: u16, body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x12, 0x34, 0x81, 0x80];
+        bytes.extend_from_slice(&questions.to_be_bytes());
+        bytes.extend_from_slice(&answers.to_be_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    /// The type, class, ttl and data length of a record.
+    fn record_head(r_type: QType, ttl: u32, data_len: u16) -> Vec<u8> {
+        let mut bytes = r_type.to_u16().to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0, 1]);
+        bytes.extend_from_slice(&ttl.to_be_bytes());
+        bytes.extend_from_slice(&data_len.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn reads_compressed_names() {
+        // www.example at 12, the answer refers back to it, and the alias target ends in a pointer
+        // to `example`.
+        let mut body = b"\x03www\x07example\x00\x00\x01\x00\x01".to_vec();
+        body.extend_from_slice(&[0xc0, 12]);
+        body.extend_from_slice(&record_head(QType::CNAME, 300, 6));
+        body.extend_from_slice(b"\x03cdn\xc0\x10");
+        body.extend_from_slice(&[0xc0, 12 + 17 + 12]);
+        body.extend_from_slice(&record_head(QType::A, 60, 4));
+        body.extend_from_slice(&[192, 0, 2, 1]);
+        let bytes = response(1, 2, &body);
+
+        let response = DNSUpstreamResponse::try_from(&bytes).unwrap();
+        assert_eq!(response.message_id, 0x1234);
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(response.answers[0].name, "www.example");
+        assert_eq!(response.answers[0].ttl, 300);
+        assert_eq!(
+            response.cname_targets().collect::<Vec<_>>(),
+            ["cdn.example"]
+        );
+        assert_eq!(response.answers[1].name, "cdn.example");
+        assert_eq!(
+            response.answers[1].data,
+            Some(RData::A(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+        assert_eq!(
+            &bytes[response.answers[1].data_range.clone()],
+            [192, 0, 2, 1]
+        );
+        assert_eq!(&bytes[response.answers[1].ttl_range()], 60u32.to_be_bytes());
+    }
+
+    #[test]
+    fn refuses_pointer_loops() {
+        // The question name points at itself.
+        let bytes = response(1, 0, &[0xc0, 12, 0, 1, 0, 1]);
+        assert!(DNSUpstreamResponse::try_from(&bytes).is_err());
+
+        // Two names pointing at each other.
+        let bytes = response(1, 0, &[0xc0, 14, 0xc0, 12, 0, 1, 0, 1]);
+        assert!(read_name(&bytes, 12).is_err());
+    }
+
+    #[test]
+    fn refuses_pointers_out_of_bounds() {
+        let bytes = response(1, 0, &[0xc0, 0xff, 0, 1, 0, 1]);
+        assert!(DNSUpstreamResponse::try_from(&bytes).is_err());
+    }
+
+    #[test]
+    fn refuses_truncated_responses() {
+        assert!(DNSUpstreamResponse::try_from(&vec![0x12, 0x34, 0x81]).is_err());
+
+        // A label running past the end.
+        let bytes = response(1, 0, b"\x07exa");
+        assert!(DNSUpstreamResponse::try_from(&bytes).is_err());
+
+        // An address with only half of its data.
+        let mut body = b"\x07example\x00\x00\x01\x00\x01\xc0\x0c".to_vec();
+        body.extend_from_slice(&record_head(QType::A, 60, 4));
+        body.extend_from_slice(&[192, 0]);
+        let bytes = response(1, 1, &body);
+        assert!(DNSUpstreamResponse::try_from(&bytes).is_err());
+
+        // More answers announced than there are.
+        let bytes = response(1, 1, b"\x07example\x00\x00\x01\x00\x01");
+        assert!(DNSUpstreamResponse::try_from(&bytes).is_err());
+    }
+
+    #[test]
+    fn lower_cases_names() {
+        let bytes = response(0, 0, b"\x03WWW\x07Example\x00");
+        assert_eq!(
+            read_name(&bytes, 12).unwrap(),
+            ("www.example".to_string(), 25)
+        );
+    }
+}
@@ -1,9 +1,15 @@
+mod blocking;
 mod dns_query_answer;
 mod dns_query_question;
+mod dns_upstream_response;
 mod q_class;
 mod q_type;
+#[allow(clippy::module_inception)]
 mod query_service;
 mod response;
 
+pub use blocking::*;
+pub use dns_query_answer::{RData, ResourceRecord};
+pub use q_type::QType;
 pub use query_service::*;
 pub use response::*;
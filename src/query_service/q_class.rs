@@ -1,4 +1,4 @@
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 pub enum QClass {
     #[default]
     IN,
@@ -17,4 +17,13 @@ impl QClass {
             _ => None,
         }
     }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            QClass::IN => 1,
+            QClass::CS => 2,
+            QClass::CH => 3,
+            QClass::HS => 4,
+        }
+    }
 }
@@ -1,4 +1,7 @@
-#[derive(Clone, Default, Debug)]
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 pub enum QType {
     #[default]
     A,
@@ -16,7 +19,10 @@ pub enum QType {
     RRSIG,
     DNSKEY,
     SSHFP,
+    SVCB,
+    HTTPS,
     SPF,
+    ANY,
     CAA,
 }
 
@@ -38,9 +44,74 @@ impl QType {
             46 => Some(QType::RRSIG),
             48 => Some(QType::DNSKEY),
             53 => Some(QType::SSHFP),
+            64 => Some(QType::SVCB),
+            65 => Some(QType::HTTPS),
             99 => Some(QType::SPF),
+            255 => Some(QType::ANY),
             257 => Some(QType::CAA),
             _ => None,
         }
     }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            QType::A => 1,
+            QType::NS => 2,
+            QType::CNAME => 5,
+            QType::SOA => 6,
+            QType::PTR => 12,
+            QType::MX => 15,
+            QType::TXT => 16,
+            QType::AAAA => 28,
+            QType::SRV => 33,
+            QType::DNAME => 39,
+            QType::OPT => 41,
+            QType::DS => 43,
+            QType::RRSIG => 46,
+            QType::DNSKEY => 48,
+            QType::SSHFP => 53,
+            QType::SVCB => 64,
+            QType::HTTPS => 65,
+            QType::SPF => 99,
+            QType::ANY => 255,
+            QType::CAA => 257,
+        }
+    }
+}
+
+impl FromStr for QType {
+    type Err = String;
+
+    /// Accepts either the mnemonic (i.e. `AAAA`) or the numeric value (i.e. `28`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(u) = s.parse::<u16>() {
+            return QType::from_u16(u).ok_or(format!("Unsupported record type {}", u));
+        }
+        (0..=u16::MAX)
+            .filter_map(QType::from_u16)
+            .find(|q_type| format!("{:?}", q_type).eq_ignore_ascii_case(s))
+            .ok_or(format!("Unknown record type {}", s))
+    }
+}
+
+impl std::fmt::Display for QType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl<'de> Deserialize<'de> for QType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Spec {
+            Number(u16),
+            Name(String),
+        }
+        let q_type = match Spec::deserialize(deserializer)? {
+            Spec::Number(u) => u.to_string(),
+            Spec::Name(name) => name,
+        };
+        q_type.parse().map_err(serde::de::Error::custom)
+    }
 }
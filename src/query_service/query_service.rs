@@ -1,16 +1,30 @@
-use chrono::Local;
-use std::collections::HashSet;
+use chrono::{DateTime, Local};
 use std::io::ErrorKind;
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{Notify, RwLock};
 use tokio::task::JoinHandle;
 
-use super::dns_query_answer::*;
+use super::blocking::BlockingMode;
+use super::dns_query_answer::{
+    DNSQueryAnswer, RData, ResourceRecord, R_CODE_NO_ERROR, R_CODE_NX_DOMAIN,
+};
 use super::dns_query_question::*;
+use super::dns_upstream_response::*;
 use super::response::Response;
+use crate::block_list::{
+    normalize_domain, BlockList, BlockListReport, BlockListSource, BlockReason, Explanation,
+    Fetched, ListDiff, ListFiles, ListFormat, ListMetadata, ListPatch, ListRules, ListStore,
+    RuleSet, Snapshot, SourceLocation,
+};
+use crate::block_page::{RecentBlocks, UnblockRequest, UnblockRequests};
+use crate::local_zone::{HostsFiles, LocalAnswer, LocalRecord, LocalZone, SpecialUse};
+use crate::policy::{
+    forward_rule_for, ClientGroup, ClientGroups, DohPolicy, ForwardRule, Pause, Pauses, QTypeRule,
+};
 
 // We shall enforce the state transition order as reflected by the structs' order below:
 #[allow(dead_code)]
@@ -23,25 +37,93 @@ pub struct Ready;
 type UpdateHandleReturnType = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
 /// The main struct used for handling DNS requests.
-/// It will take a path to the local db of a block list. This list will be periodically updated
+/// It will take the directory of the local db, where copies of the lists are kept, along
+/// with the sources to be merged into the active rule set. The lists will be periodically updated
 /// and thus will be guarded behind a read write lock.
 pub struct QueryService<State = NotIndexed> {
-    db_file_path: PathBuf,
-    nono_list: Arc<RwLock<HashSet<String>>>,
+    list_store: ListStore,
+    nono_list: Arc<RwLock<RuleSet>>,
+    blocking_mode: BlockingMode,
+    qtype_rules: Vec<QTypeRule>,
+    client_groups: ClientGroups,
+    local_zone: LocalZone,
+    forward_rules: Vec<ForwardRule>,
+    safe_search: bool,
+    doh_policy: DohPolicy,
+    pauses: Pauses,
+    unblock_requests: UnblockRequests,
+    recent_blocks: RecentBlocks,
+    refresh_trigger: Arc<Notify>,
     update_handle: Option<tokio::task::JoinHandle<UpdateHandleReturnType>>,
     state: PhantomData<State>,
 }
 
 impl QueryService<NotIndexed> {
-    pub fn new(db_file_path: PathBuf) -> Self {
+    pub fn new(db_dir: PathBuf, sources: Vec<BlockListSource>) -> Self {
         QueryService {
-            db_file_path,
-            nono_list: Arc::new(RwLock::new(HashSet::new())),
+            list_store: ListStore::new(db_dir),
+            nono_list: Arc::new(RwLock::new(RuleSet::new(sources))),
+            blocking_mode: BlockingMode::default(),
+            qtype_rules: Vec::new(),
+            client_groups: ClientGroups::default(),
+            local_zone: LocalZone::default(),
+            forward_rules: Vec::new(),
+            safe_search: false,
+            doh_policy: DohPolicy::default(),
+            pauses: Pauses::default(),
+            unblock_requests: UnblockRequests::default(),
+            recent_blocks: RecentBlocks::default(),
+            refresh_trigger: Arc::new(Notify::new()),
             update_handle: None,
             state: PhantomData,
         }
     }
 
+    pub fn with_blocking_mode(mut self, blocking_mode: BlockingMode) -> Self {
+        self.blocking_mode = blocking_mode;
+        self
+    }
+
+    pub fn with_qtype_rules(mut self, qtype_rules: Vec<QTypeRule>) -> Self {
+        self.qtype_rules = qtype_rules;
+        self
+    }
+
+    pub fn with_client_groups(mut self, client_groups: Vec<ClientGroup>) -> Self {
+        self.client_groups = ClientGroups::new(client_groups);
+        self
+    }
+
+    pub fn with_local_zone(
+        mut self,
+        local_records: Vec<LocalRecord>,
+        hosts_files: Vec<PathBuf>,
+    ) -> Self {
+        self.local_zone = LocalZone::new(local_records, HostsFiles::new(hosts_files));
+        self
+    }
+
+    pub fn with_forward_rules(mut self, forward_rules: Vec<ForwardRule>) -> Self {
+        self.forward_rules = forward_rules;
+        self
+    }
+
+    pub fn with_safe_search(mut self, safe_search: bool) -> Self {
+        self.safe_search = safe_search;
+        self
+    }
+
+    /// How many copies of each list to keep around to roll back to.
+    pub fn with_snapshot_retention(mut self, retention: usize) -> Self {
+        self.list_store = self.list_store.with_retention(retention);
+        self
+    }
+
+    pub fn with_doh_policy(mut self, doh_policy: DohPolicy) -> Self {
+        self.doh_policy = doh_policy;
+        self
+    }
+
     pub async fn index_db(
         self,
     ) -> Result<
@@ -49,24 +131,63 @@ impl QueryService<NotIndexed> {
         Box<dyn std::error::Error + Send + Sync>,
     > {
         let QueryService {
-            db_file_path,
+            list_store,
             nono_list,
+            blocking_mode,
+            qtype_rules,
+            client_groups,
+            local_zone,
+            forward_rules,
+            safe_search,
+            doh_policy,
+            pauses,
+            unblock_requests,
+            recent_blocks,
+            refresh_trigger,
             update_handle,
             ..
         } = self;
 
-        {
-            let mut nono_list = nono_list.write().await;
-            let db_file = tokio::fs::read(&db_file_path).await?;
-            let content = String::from_utf8(db_file)?;
-            for line in content.lines() {
-                nono_list.insert(line.to_string());
+        local_zone.hosts_files().reload().await;
+
+        // Lists that live on disk can be indexed right away, remote ones start out with their
+        // last stored copy (if any) and are left to the update task.
+        let sources: Vec<BlockListSource> = nono_list
+            .read()
+            .await
+            .lists()
+            .iter()
+            .filter(|list| list.source.enabled)
+            .map(|list| list.source.clone())
+            .collect();
+        for source in sources {
+            let name = source.name.clone();
+            let loaded = match source.location {
+                SourceLocation::Path(_) => refresh_source(&nono_list, &list_store, source).await,
+                SourceLocation::Url(_) => load_snapshot(&nono_list, &list_store, &source)
+                    .await
+                    .map(|_| ()),
+            };
+            // A broken list is no reason not to start, the update task retries it.
+            if let Err(e) = loaded {
+                record_failure(&nono_list, &name, e).await;
             }
         }
 
         Ok(QueryService {
-            db_file_path,
+            list_store,
             nono_list,
+            blocking_mode,
+            qtype_rules,
+            client_groups,
+            local_zone,
+            forward_rules,
+            safe_search,
+            doh_policy,
+            pauses,
+            unblock_requests,
+            recent_blocks,
+            refresh_trigger,
             update_handle,
             state: PhantomData,
         })
@@ -78,56 +199,67 @@ impl QueryService<NotRegisteredForPeriodicUpdate> {
         self,
     ) -> Result<QueryService<Ready>, Box<dyn std::error::Error + Send + Sync>> {
         let QueryService {
-            db_file_path,
+            list_store,
             nono_list,
+            blocking_mode,
+            qtype_rules,
+            client_groups,
+            local_zone,
+            forward_rules,
+            safe_search,
+            doh_policy,
+            pauses,
+            unblock_requests,
+            recent_blocks,
+            refresh_trigger,
             ..
         } = self;
 
+        tokio::task::spawn(local_zone.hosts_files().watch());
+
         let nono_list_ref = nono_list.clone();
-        let db_file_path_clone = db_file_path.clone();
+        let list_store_clone = list_store.clone();
+        let refresh_trigger_ref = refresh_trigger.clone();
         let update_handle = {
             let handle = tokio::task::spawn(async move {
                 // TODO: log this instead
                 println!("Spawning periodic update task");
+                let mut list_files = ListFiles::new();
                 loop {
-                    tokio::select! {
-                        _ = tokio::time::sleep(std::time::Duration::from_secs(60 * 60 * 24 * 7)) => {}
-                        // TODO: add a notify here to wake this task up from sleep upon
-                        // We can accept the notify as an arg from new()
-                    }
-                    // TODO: add actual db file update task here
-                    // Refresh once every week.
-                    let mut nono_list: HashSet<String> = HashSet::new();
-
-                    // Download it from easylist
-                    let response =
-                        reqwest::get("https://easylist.to/easylist/easylist.txt").await?;
-                    let list_content = response.text().await?;
-
-                    // Populate the new map with it
-                    for line in list_content.lines() {
-                        nono_list.insert(line.to_string());
+                    // Sources may have changed since the last time around.
+                    let watched = ListFiles::watched(&*nono_list_ref.read().await);
+                    list_files.watch(watched).await;
+
+                    let due_sources = { nono_list_ref.read().await.due_sources(Local::now()) };
+                    for source in due_sources {
+                        let name = source.name.clone();
+                        if let Err(e) =
+                            refresh_source(&nono_list_ref, &list_store_clone, source).await
+                        {
+                            record_failure(&nono_list_ref, &name, e).await;
+                        }
                     }
 
-                    // swap
-                    {
-                        let mut nono_list_write_ref = nono_list_ref.write().await;
-                        std::mem::swap(&mut *nono_list_write_ref, &mut nono_list);
+                    // Sleep until the next list is due. With nothing enabled there is nothing
+                    // to wait for, so check back in an hour in case sources have changed.
+                    let sleep_duration = match nono_list_ref.read().await.next_refresh() {
+                        Some(next_refresh) => {
+                            (next_refresh - Local::now()).to_std().unwrap_or_default()
+                        }
+                        None => std::time::Duration::from_secs(60 * 60),
+                    };
+                    tokio::select! {
+                        _ = tokio::time::sleep(sleep_duration) => {}
+                        _ = refresh_trigger_ref.notified() => {}
+                        changed = list_files.changed() => {
+                            let mut nono_list = nono_list_ref.write().await;
+                            for name in changed {
+                                // TODO: log this
+                                println!("List file of {} changed, reloading it", name);
+                                nono_list.request_refresh(Some(&name));
+                            }
+                        }
                     }
-                    // TODO: log this
-                    let time_now = Local::now().format("%y-%m-%d-%H:%M:%S");
-                    println!("Block list update completed at {}", time_now);
-
-                    // Replace the file for record keeping
-                    let db_file_dir = db_file_path_clone.parent().ok_or(std::io::Error::new(
-                        ErrorKind::NotFound,
-                        "Block list file parent not found",
-                    ))?;
-                    let new_db_file_path = db_file_dir.join(format!("block_list_{}.txt", time_now));
-                    let mut file = tokio::fs::File::create(&new_db_file_path).await?;
-                    file.write_all(list_content.as_bytes()).await?;
-                    // TODO: log this
-                    println!("Block list file {} updated", time_now);
                 }
 
                 #[allow(unreachable_code)]
@@ -138,8 +270,19 @@ impl QueryService<NotRegisteredForPeriodicUpdate> {
         };
 
         Ok(QueryService {
-            db_file_path,
+            list_store,
             nono_list,
+            blocking_mode,
+            qtype_rules,
+            client_groups,
+            local_zone,
+            forward_rules,
+            safe_search,
+            doh_policy,
+            pauses,
+            unblock_requests,
+            recent_blocks,
+            refresh_trigger,
             update_handle,
             state: PhantomData,
         })
@@ -149,13 +292,492 @@ impl QueryService<NotRegisteredForPeriodicUpdate> {
 impl QueryService<Ready> {
     /// This is the main entry point for request processing.
     /// The request shall be read into a byte vector.
+    /// The client's address decides which group's policy applies.
     pub async fn process_bytes(
         &self,
         input_bytes: &Vec<u8>,
+        client_addr: SocketAddr,
     ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
         let query = DNSQueryQuestion::try_from(input_bytes)?;
         println!("Query: {:?}", query);
-        Ok(Response::Miss(query.message_id))
+        let response = self.answer(&query, client_addr).await;
+        self.remember_block(&query, client_addr, &response).await;
+        Ok(response)
+    }
+
+    async fn answer(&self, query: &DNSQueryQuestion<'_>, client_addr: SocketAddr) -> Response {
+        let group = self.client_groups.resolve(&client_addr.ip()).await;
+        let paused = self.paused_until(group).await.is_some();
+
+        let domain = query.q_name_array.join(".").to_ascii_lowercase();
+        if let Some((idx, rule)) = self
+            .qtype_rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| !paused && rule.matches(query.q_type, &domain))
+        {
+            let reason = BlockReason::policy("qtype_rules", idx + 1, rule.to_string());
+            // TODO: log this
+            println!("Blocked {} by {}", domain, reason);
+            return match rule.action.blocking_mode() {
+                Some(blocking_mode) => Response::Blocked(blocking_mode.answer(query), reason),
+                None => Response::Dropped(reason),
+            };
+        }
+
+        // Canaries keep being answered while blocking is paused, clients that saw them resolve
+        // might not check back for a while.
+        if let Some((idx, canary)) = self.doh_policy.canary(&domain) {
+            let reason = BlockReason::policy("doh.canary_domains", idx, canary.to_string());
+            // TODO: log this
+            println!("Blocked {} by {}", domain, reason);
+            return Response::Blocked(BlockingMode::NxDomain.answer(query), reason);
+        }
+
+        // Our own records take precedence over the block lists.
+        if let Some(local) = self.local_zone.lookup(&domain, query.q_type, false).await {
+            return self.answer_locally(query, &domain, local);
+        }
+
+        // Special-use names stay here, unless they are explicitly forwarded somewhere.
+        let forward_rule = forward_rule_for(&self.forward_rules, &domain);
+        if let (None, Some(special_use)) = (forward_rule, SpecialUse::of(&domain)) {
+            // TODO: log this
+            println!("Answered {} locally as special-use name", domain);
+            let r_code = match special_use {
+                SpecialUse::Loopback => R_CODE_NO_ERROR,
+                SpecialUse::NonExistent => R_CODE_NX_DOMAIN,
+            };
+            let answers = special_use.answers(&domain, query.q_type);
+            let answer = DNSQueryAnswer::reply_to(query, r_code, answers).authoritative();
+            return Response::Hit(answer.into());
+        }
+
+        if let (false, Some((line, resolver))) = (paused, self.doh_policy.resolver(&domain)) {
+            if group.is_none_or(|group| group.allows(&domain).is_none()) {
+                let reason = BlockReason::policy("doh.resolvers", line, resolver.to_string());
+                // TODO: log this
+                println!("Blocked {} for {} by {}", domain, client_addr, reason);
+                return Response::Blocked(self.blocking_mode_for(group).answer(query), reason);
+            }
+        }
+
+        if !paused {
+            let explanation = explain_for(&*self.nono_list.read().await, &domain, group);
+            if let (true, Some(reason)) = (explanation.blocked, explanation.blocked_by) {
+                // TODO: log this
+                println!("Blocked {} for {} by {}", domain, client_addr, reason);
+                return Response::Blocked(self.blocking_mode_for(group).answer(query), reason);
+            }
+        }
+
+        // Unlike our own records, safe search does not get around blocking.
+        if self.safe_search_for(group) {
+            if let Some(local) = self.local_zone.lookup(&domain, query.q_type, true).await {
+                return self.answer_locally(query, &domain, local);
+            }
+        }
+
+        Response::Miss(
+            query.message_id,
+            forward_rule.and_then(|rule| rule.upstream),
+        )
+    }
+
+    /// Inspects the response the upstream server gave to a query we let through.
+    /// Trackers tend to hide behind first party names that alias to them, so the response is
+    /// blocked as a whole if anything along its CNAME chain is on the block list. Addresses in
+    /// blocked ranges are replaced according to the blocking mode. Responses that cannot be
+    /// inspected are passed on as they are.
+    pub async fn process_upstream_bytes(
+        &self,
+        query_bytes: &Vec<u8>,
+        response_bytes: &Vec<u8>,
+        client_addr: SocketAddr,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let query = DNSQueryQuestion::try_from(query_bytes)?;
+        let response = self
+            .inspect_upstream(&query, response_bytes, client_addr)
+            .await;
+        self.remember_block(&query, client_addr, &response).await;
+        Ok(response)
+    }
+
+    async fn inspect_upstream(
+        &self,
+        query: &DNSQueryQuestion<'_>,
+        response_bytes: &Vec<u8>,
+        client_addr: SocketAddr,
+    ) -> Response {
+        let response = match DNSUpstreamResponse::try_from(response_bytes) {
+            Ok(response) => response,
+            Err(e) => {
+                // TODO: log this
+                println!("Upstream response could not be inspected: {}", e);
+                return Response::Hit(response_bytes.clone());
+            }
+        };
+        let domain = query.q_name_array.join(".").to_ascii_lowercase();
+        let group = self.client_groups.resolve(&client_addr.ip()).await;
+        // Responses to rewritten queries are about the target of the alias, the client asked
+        // about the original name.
+        let safe_search = self.safe_search_for(group);
+        if let Some(LocalAnswer {
+            records: aliases,
+            external_target: Some(_),
+        }) = self
+            .local_zone
+            .lookup(&domain, query.q_type, safe_search)
+            .await
+        {
+            let answers = aliases
+                .into_iter()
+                .chain(response.answers.iter().filter_map(|record| {
+                    record.data.clone().map(|data| ResourceRecord {
+                        name: record.name.clone(),
+                        ttl: record.ttl,
+                        data,
+                    })
+                }))
+                .collect();
+            let answer = DNSQueryAnswer::reply_to(query, response.r_code, answers);
+            return Response::Hit(answer.into());
+        }
+        // Whatever the answer turns out to be, an allowlisted domain is let through. So is
+        // everything while blocking is paused.
+        if group.is_some_and(|group| group.allows(&domain).is_some())
+            || self.paused_until(group).await.is_some()
+        {
+            return Response::Hit(response_bytes.clone());
+        }
+        let selection = group.and_then(|group| group.lists.as_deref());
+
+        let cloaked = {
+            let nono_list = self.nono_list.read().await;
+            response.cname_targets().find_map(|target| {
+                let explanation = explain_for(&nono_list, target, group);
+                match (explanation.blocked, explanation.blocked_by) {
+                    (true, Some(mut reason)) => {
+                        reason.cloaked_target = Some(target.to_string());
+                        Some(reason)
+                    }
+                    _ => None,
+                }
+            })
+        };
+        if let Some(reason) = cloaked {
+            // TODO: log this
+            println!("Blocked {} for {} by {}", domain, client_addr, reason);
+            return Response::Blocked(self.blocking_mode_for(group).answer(query), reason);
+        }
+
+        // Addresses in blocked ranges get replaced, whatever name they were looked up by.
+        let blocked_ips: Vec<(&UpstreamRecord, BlockReason)> = {
+            let nono_list = self.nono_list.read().await;
+            response
+                .answers
+                .iter()
+                .filter_map(|record| {
+                    let ip: IpAddr = match record.data {
+                        Some(RData::A(ip)) => ip.into(),
+                        Some(RData::AAAA(ip)) => ip.into(),
+                        _ => return None,
+                    };
+                    nono_list
+                        .find_ip(&ip, selection)
+                        .map(|reason| (record, reason))
+                })
+                .collect()
+        };
+        if let Some((_, reason)) = blocked_ips.first() {
+            for (_, reason) in &blocked_ips {
+                // TODO: log this
+                println!("Blocked {} for {} by {}", domain, client_addr, reason);
+            }
+            let records: Vec<&UpstreamRecord> =
+                blocked_ips.iter().map(|(record, _)| *record).collect();
+            return Response::Blocked(
+                self.blocking_mode_for(group)
+                    .patch_answers(query, response_bytes, &records),
+                reason.clone(),
+            );
+        }
+
+        Response::Hit(response_bytes.clone())
+    }
+
+    /// Tells which rule (if any) blocks the given domain and which exception (if any) lets it
+    /// through. Without a client the domain is looked at as for clients in no group.
+    pub async fn explain(&self, domain: &str, client: Option<IpAddr>) -> Explanation {
+        let domain = normalize_domain(domain).unwrap_or_else(|| domain.to_string());
+        let group = match client {
+            Some(client) => self.client_groups.resolve(&client).await,
+            None => None,
+        };
+        let mut explanation = explain_for(&*self.nono_list.read().await, &domain, group);
+        explanation.paused_until = self.paused_until(group).await;
+        if explanation.paused_until.is_some() {
+            explanation.blocked = false;
+        }
+        explanation
+    }
+
+    /// Pauses blocking for a while, for every client or for the clients of a single group.
+    pub async fn pause(&self, group: Option<String>, duration: Duration) -> Result<Pause, String> {
+        if let Some(group) = &group {
+            if !self.client_groups.contains(group) {
+                return Err(format!("There is no group {}", group));
+            }
+        }
+        let pause = self.pauses.pause(group, duration).await?;
+        // TODO: log this
+        println!("{}", pause);
+        Ok(pause)
+    }
+
+    /// Ends a pause early. Returns false if there was none.
+    pub async fn resume(&self, group: Option<&str>) -> bool {
+        let resumed = self.pauses.resume(group).await;
+        if resumed {
+            // TODO: log this
+            match group {
+                Some(group) => println!("Blocking resumed for group {}", group),
+                None => println!("Blocking resumed"),
+            }
+        }
+        resumed
+    }
+
+    pub async fn pauses(&self) -> Vec<Pause> {
+        self.pauses.active().await
+    }
+
+    /// Why the domain was blocked for the client: the decision of its recent block if there was
+    /// one, which also covers blocks made by the upstream answer, otherwise what the rule set
+    /// has to say about the domain.
+    pub async fn explain_block(&self, domain: &str, client: IpAddr) -> Explanation {
+        let Some(reason) = self.recent_blocks.get(client, domain).await else {
+            return self.explain(domain, Some(client)).await;
+        };
+        let group = self.client_groups.resolve(&client).await;
+        let paused_until = self.paused_until(group).await;
+        Explanation {
+            domain: domain.to_string(),
+            group: group.map(|group| group.name.clone()),
+            blocked: paused_until.is_none(),
+            blocked_by: Some(reason),
+            allowed_by: None,
+            paused_until,
+        }
+    }
+
+    /// Records that the client would like the domain unblocked.
+    pub async fn request_unblock(&self, domain: &str, client: IpAddr) {
+        let explanation = self.explain_block(domain, client).await;
+        // TODO: log this
+        println!("{} asked for {} to be unblocked", client, domain);
+        self.unblock_requests
+            .add(UnblockRequest {
+                domain: domain.to_string(),
+                client,
+                group: explanation.group,
+                blocked_by: explanation.blocked_by,
+                requested_at: Local::now(),
+            })
+            .await;
+    }
+
+    pub async fn unblock_requests(&self) -> Vec<UnblockRequest> {
+        self.unblock_requests.all().await
+    }
+
+    /// Whether any client gets blocked domains answered with the address of the block page.
+    pub fn serves_block_page(&self) -> bool {
+        std::iter::once(self.blocking_mode)
+            .chain(self.client_groups.blocking_modes())
+            .any(|blocking_mode| matches!(blocking_mode, BlockingMode::Ip(_)))
+    }
+
+    /// Keeps the reason of a block around for the block page.
+    async fn remember_block(
+        &self,
+        query: &DNSQueryQuestion<'_>,
+        client_addr: SocketAddr,
+        response: &Response,
+    ) {
+        if let Response::Blocked(_, reason) = response {
+            let domain = query.q_name_array.join(".");
+            let domain = normalize_domain(&domain).unwrap_or(domain);
+            self.recent_blocks
+                .add(client_addr.ip().to_canonical(), domain, reason.clone())
+                .await;
+        }
+    }
+
+    async fn paused_until(&self, group: Option<&ClientGroup>) -> Option<DateTime<Local>> {
+        self.pauses
+            .paused_until(group.map(|group| group.name.as_str()))
+            .await
+    }
+
+    /// Answers from the local zone. Aliases leading out of the zone are answered once the
+    /// upstream answer for their target is back, in front of it.
+    fn answer_locally(
+        &self,
+        query: &DNSQueryQuestion<'_>,
+        domain: &str,
+        local: LocalAnswer,
+    ) -> Response {
+        if let Some(target) = local.external_target {
+            // TODO: log this
+            println!("Aliased {} to {}, looking it up upstream", domain, target);
+            let upstream =
+                forward_rule_for(&self.forward_rules, &target).and_then(|rule| rule.upstream);
+            return Response::Rewritten(query.message_id, query.renamed(&target), upstream);
+        }
+        // TODO: log this
+        println!("Answered {} locally", domain);
+        let answer =
+            DNSQueryAnswer::reply_to(query, R_CODE_NO_ERROR, local.records).authoritative();
+        Response::Hit(answer.into())
+    }
+
+    fn safe_search_for(&self, group: Option<&ClientGroup>) -> bool {
+        group
+            .and_then(|group| group.safe_search)
+            .unwrap_or(self.safe_search)
+    }
+
+    fn blocking_mode_for(&self, group: Option<&ClientGroup>) -> BlockingMode {
+        group
+            .and_then(|group| group.blocking_mode)
+            .unwrap_or(self.blocking_mode)
+    }
+
+    /// Sources and their status, in the order they are consulted.
+    pub async fn block_lists(&self) -> Vec<BlockListReport> {
+        self.nono_list.read().await.report()
+    }
+
+    /// Adds a new source or reconfigures an existing one with the same name.
+    /// The update task is woken up to pick it up. Sources are named in the files stored for
+    /// them, so names are limited to letters, digits, `-`, `_` and `.`.
+    pub async fn upsert_source(&self, source: BlockListSource) -> Result<(), String> {
+        let valid_name = !source.name.is_empty()
+            && source
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_name {
+            return Err(format!("Invalid list name `{}`", source.name));
+        }
+        self.nono_list.write().await.upsert_source(source);
+        self.refresh_trigger.notify_one();
+        Ok(())
+    }
+
+    /// The configuration of the named source.
+    pub async fn source(&self, name: &str) -> Option<BlockListSource> {
+        let nono_list = self.nono_list.read().await;
+        nono_list.get(name).map(|list| list.source.clone())
+    }
+
+    /// Refreshes the named list, or every list, right away rather than when it is due.
+    /// Returns false if there is no list with the given name.
+    pub async fn refresh(&self, name: Option<&str>) -> bool {
+        if !self.nono_list.write().await.request_refresh(name) {
+            return false;
+        }
+        self.refresh_trigger.notify_one();
+        true
+    }
+
+    /// The stored copies of a list, newest first.
+    pub async fn snapshots(&self, name: &str) -> Result<Vec<Snapshot>, String> {
+        if self.nono_list.read().await.get(name).is_none() {
+            return Err(format!("No such list {}", name));
+        }
+        self.list_store
+            .snapshots(name)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// How the list changed with the update that produced the given snapshot, or the snapshot
+    /// in use if none is given.
+    pub async fn diff(&self, name: &str, snapshot: Option<&str>) -> Result<ListDiff, String> {
+        let in_use = match self.nono_list.read().await.get(name) {
+            Some(list) => list.status.snapshot.clone(),
+            None => return Err(format!("No such list {}", name)),
+        };
+        let id = match snapshot {
+            Some(id) => id.to_string(),
+            None => {
+                in_use
+                    .and_then(|path| Snapshot::from_path(&path, name))
+                    .ok_or("No snapshot in use")?
+                    .id
+            }
+        };
+        self.list_store
+            .diff(name, &id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or(format!("No diff for snapshot {}, the list was new", id))
+    }
+
+    /// Puts a stored copy of a list back in use: the one with the given id, or the one before
+    /// the copy in use. It stays in use until the list is refreshed next, which downloads the
+    /// list in full.
+    pub async fn rollback(&self, name: &str, to: Option<&str>) -> Result<Snapshot, String> {
+        let (format, in_use) = match self.nono_list.read().await.get(name) {
+            Some(list) => (list.source.format, list.status.snapshot.clone()),
+            None => return Err(format!("No such list {}", name)),
+        };
+        let snapshots = self.snapshots(name).await?;
+        let snapshot = match to {
+            Some(to) => snapshots.into_iter().find(|snapshot| snapshot.id == to),
+            None => {
+                let in_use = in_use.ok_or("No snapshot in use to roll back from")?;
+                snapshots
+                    .into_iter()
+                    .skip_while(|snapshot| snapshot.path != in_use)
+                    .nth(1)
+            }
+        }
+        .ok_or("No such snapshot")?;
+        let (rules, metadata) = read_snapshot(&snapshot, format)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut nono_list = self.nono_list.write().await;
+        let list = nono_list
+            .get_mut(name)
+            .ok_or(format!("No such list {}", name))?;
+        use_snapshot(list, &snapshot, rules, metadata);
+        // Revalidating would keep the rolled back copy for as long as the list does not change.
+        list.status.validators = Default::default();
+        list.status.last_downloaded = None;
+        list.status.last_attempt = Some(Local::now());
+        // TODO: log this
+        println!("Block list {} rolled back to {}", name, snapshot.id);
+        Ok(snapshot)
+    }
+
+    /// Stops using the named source. Its stored downloads are left in place.
+    pub async fn remove_source(&self, name: &str) -> bool {
+        self.nono_list.write().await.remove_source(name).is_some()
+    }
+
+    /// Disabled sources neither block nor get updated. Enabled ones are refreshed if due.
+    /// Returns false if there is no source with the given name.
+    pub async fn set_source_enabled(&self, name: &str, enabled: bool) -> bool {
+        match self.nono_list.write().await.get_mut(name) {
+            Some(list) => list.source.enabled = enabled,
+            None => return false,
+        }
+        self.refresh_trigger.notify_one();
+        true
     }
 
     pub fn gib_update_task_handle(
@@ -164,3 +786,311 @@ impl QueryService<Ready> {
         self.update_handle.take()
     }
 }
+
+/// Looks at the domain the way it applies to the given group: only the group's lists are
+/// consulted (or everything is blocked, if so configured) and its allowlist trumps them.
+fn explain_for(nono_list: &RuleSet, domain: &str, group: Option<&ClientGroup>) -> Explanation {
+    let Some(group) = group else {
+        return nono_list.explain(domain, None);
+    };
+    let mut explanation = nono_list.explain(domain, group.lists.as_deref());
+    explanation.group = Some(group.name.clone());
+    if group.block_all && !explanation.blocked {
+        explanation.blocked = true;
+        explanation.blocked_by = Some(BlockReason::policy(
+            &format!("{}.block_all", group.name),
+            1,
+            "*".to_string(),
+        ));
+    }
+    if let Some((line, allowed)) = group.allows(domain) {
+        explanation.blocked = false;
+        explanation.allowed_by = Some(BlockReason::policy(
+            &format!("{}.allowlist", group.name),
+            line,
+            allowed.to_string(),
+        ));
+    }
+    explanation
+}
+
+/// Puts the newest usable snapshot of a remote list in use, so that after a restart the list
+/// blocks right away rather than once it is downloaded again. Its refresh is due as if the
+/// snapshot had just been downloaded. Returns false if there is no usable snapshot.
+async fn load_snapshot(
+    nono_list: &RwLock<RuleSet>,
+    list_store: &ListStore,
+    source: &BlockListSource,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    for snapshot in list_store.snapshots(&source.name).await? {
+        let (rules, metadata) = match read_snapshot(&snapshot, source.format).await {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                // TODO: log this
+                println!("Skipping {}: {}", snapshot.path.display(), e);
+                continue;
+            }
+        };
+
+        let mut nono_list = nono_list.write().await;
+        let Some(list) = nono_list.get_mut(&source.name) else {
+            return Ok(false);
+        };
+        use_snapshot(list, &snapshot, rules, metadata);
+        list.status.last_attempt = Some(snapshot.taken_at);
+        list.status.last_downloaded = Some(snapshot.taken_at);
+        // TODO: log this
+        println!(
+            "Block list {} loaded from {}",
+            source.name,
+            snapshot.path.display()
+        );
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Parses a stored copy of a list. Copies that are corrupted or have no rules are refused.
+async fn read_snapshot(
+    snapshot: &Snapshot,
+    format: ListFormat,
+) -> Result<(ListRules, ListMetadata), Box<dyn std::error::Error + Send + Sync>> {
+    let content = snapshot.read().await?;
+    let rules = ListRules::parse(&content, format);
+    if rules.is_empty() {
+        return Err("No rules".into());
+    }
+    Ok((rules, ListMetadata::parse(&content)))
+}
+
+fn use_snapshot(
+    list: &mut BlockList,
+    snapshot: &Snapshot,
+    rules: ListRules,
+    metadata: ListMetadata,
+) {
+    list.status.last_updated = Some(snapshot.taken_at);
+    list.status.entry_count = rules.len();
+    list.status.metadata = metadata;
+    list.status.snapshot = Some(snapshot.path.clone());
+    list.rules = rules;
+}
+
+/// Fetches and parses the given source and swaps the result into the rule set.
+/// Lists are also written into the db directory for record keeping, local ones only when they
+/// changed since they were last stored. Lists that have not changed since their last download
+/// are left as they are.
+async fn refresh_source(
+    nono_list: &RwLock<RuleSet>,
+    list_store: &ListStore,
+    source: BlockListSource,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Only what is loaded can be revalidated or patched.
+    let (validators, patchable, previous_len) = {
+        let nono_list = nono_list.read().await;
+        let loaded = nono_list
+            .lists()
+            .iter()
+            .find(|list| list.source.name == source.name && list.status.last_updated.is_some());
+        (
+            loaded
+                .map(|list| list.status.validators.clone())
+                .unwrap_or_default(),
+            loaded
+                .filter(|list| !list.download_due(Local::now()))
+                .and_then(|list| {
+                    Some((
+                        list.status.metadata.diff_path.clone()?,
+                        list.status.snapshot.clone()?,
+                    ))
+                }),
+            loaded.map_or(0, |list| list.status.entry_count),
+        )
+    };
+
+    let mut patched = None;
+    if let Some((diff_path, snapshot)) = patchable {
+        match patch_source(&source, &diff_path, &snapshot).await {
+            Ok(fetched) => patched = Some(fetched),
+            Err(e) => {
+                // TODO: log this
+                println!(
+                    "Block list {} could not be patched, downloading it in full: {}",
+                    source.name, e
+                );
+            }
+        }
+    }
+    let downloaded = patched.is_none();
+    let fetched = match patched {
+        Some(fetched) => fetched,
+        None => source.fetch(&validators).await?,
+    };
+    let (list_content, content_type, validators) = match fetched {
+        Fetched::Modified {
+            content,
+            content_type,
+            validators,
+        } => (content, content_type, Some(validators)),
+        Fetched::Patched(content) => (content, None, None),
+        Fetched::NotModified => {
+            if let Some(list) = nono_list.write().await.get_mut(&source.name) {
+                let time_now = Local::now();
+                if downloaded {
+                    list.status.last_downloaded = Some(time_now);
+                }
+                list.status.last_attempt = Some(time_now);
+                list.status.last_error = None;
+                list.status.failures = 0;
+                list.refresh_requested = false;
+            }
+            // TODO: log this
+            println!("Block list {} has not changed", source.name);
+            return Ok(());
+        }
+    };
+    let rules = ListRules::parse(&list_content, source.format);
+    // Local lists are edited by hand and whatever they hold is meant to be used.
+    if let SourceLocation::Url(_) = source.location {
+        source
+            .validation
+            .check(&list_content, content_type.as_deref(), &rules, previous_len)
+            .map_err(|reason| format!("Update rejected: {}", reason))?;
+    }
+    let metadata = ListMetadata::parse(&list_content);
+
+    // Only lists that were in use before have changed.
+    let time_now = Local::now();
+    let diff = nono_list
+        .read()
+        .await
+        .get(&source.name)
+        .filter(|list| list.status.last_updated.is_some())
+        .map(|list| ListDiff::between(&source.name, time_now, &list.rules, &rules));
+
+    // swap
+    {
+        let mut nono_list = nono_list.write().await;
+        let list = nono_list.get_mut(&source.name).ok_or(std::io::Error::new(
+            ErrorKind::NotFound,
+            format!("Block list {} was removed during update", source.name),
+        ))?;
+        list.status.last_updated = Some(time_now);
+        list.status.last_attempt = Some(time_now);
+        list.status.entry_count = rules.len();
+        list.status.last_error = None;
+        list.status.failures = 0;
+        // Patches leave the validators of the last download as they are.
+        if let Some(validators) = validators {
+            list.status.validators = validators;
+            list.status.last_downloaded = Some(time_now);
+        }
+        list.status.metadata = metadata;
+        list.status.last_change = diff.as_ref().map(ListDiff::summary);
+        list.refresh_requested = false;
+        list.rules = rules;
+    }
+    // TODO: log this
+    println!(
+        "Block list {} update completed at {}{}",
+        source.name,
+        time_now.format("%y-%m-%d-%H:%M:%S"),
+        diff.as_ref()
+            .map(|diff| format!(
+                ", {} added, {} removed",
+                diff.added.len(),
+                diff.removed.len()
+            ))
+            .unwrap_or_default()
+    );
+
+    // Local lists are read again on every refresh and on startup, mostly unchanged.
+    let unchanged = match source.location {
+        SourceLocation::Path(_) => list_store
+            .newest_with(&source.name, &list_content)
+            .await
+            .unwrap_or_default(),
+        SourceLocation::Url(_) => None,
+    };
+    if let Some(snapshot) = unchanged {
+        if let Some(list) = nono_list.write().await.get_mut(&source.name) {
+            list.status.snapshot = Some(snapshot.path);
+        }
+    } else {
+        // The new rules are in use already, failing to keep a copy of them does not undo that.
+        match list_store
+            .store(&source.name, time_now, &list_content)
+            .await
+        {
+            Ok(snapshot) => {
+                if let Some(diff) = &diff {
+                    if let Err(e) = list_store.store_diff(&snapshot, diff).await {
+                        // TODO: log this
+                        println!(
+                            "Diff of block list {} could not be stored: {}",
+                            source.name, e
+                        );
+                    }
+                }
+                if let Some(list) = nono_list.write().await.get_mut(&source.name) {
+                    list.status.snapshot = Some(snapshot.path);
+                }
+            }
+            Err(e) => {
+                // TODO: log this
+                println!("Block list {} could not be stored: {}", source.name, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the differential update published at the diff path to the stored copy of the list.
+/// The anchor of the path, if any, names the diff of the list within the patch file.
+async fn patch_source(
+    source: &BlockListSource,
+    diff_path: &str,
+    snapshot: &Path,
+) -> Result<Fetched, Box<dyn std::error::Error + Send + Sync>> {
+    let (path, name) = match diff_path.split_once('#') {
+        Some((path, name)) => (path, Some(name)),
+        None => (diff_path, None),
+    };
+    let Some(patch_file) = source.fetch_patch(path).await? else {
+        return Ok(Fetched::NotModified);
+    };
+    let content = Snapshot::from_path(snapshot, &source.name)
+        .ok_or("Not a snapshot of the list")?
+        .read()
+        .await?;
+    let patched = ListPatch::parse(&patch_file, name)?.apply(&content)?;
+    // TODO: log this
+    println!("Block list {} patched with {}", source.name, diff_path);
+    Ok(Fetched::Patched(patched))
+}
+
+/// Notes a failed update of the list. Its rules are left as they are, so the last good version
+/// keeps being served until the retry succeeds.
+async fn record_failure(
+    nono_list: &RwLock<RuleSet>,
+    name: &str,
+    error: Box<dyn std::error::Error + Send + Sync>,
+) {
+    let mut nono_list = nono_list.write().await;
+    let Some(list) = nono_list.get_mut(name) else {
+        return;
+    };
+    list.refresh_requested = false;
+    list.status.last_attempt = Some(Local::now());
+    list.status.last_error = Some(error.to_string());
+    list.status.failures += 1;
+    // TODO: log this
+    println!(
+        "Block list {} failed to update ({} in a row), retrying at {}: {}",
+        name,
+        list.status.failures,
+        list.next_refresh().format("%H:%M:%S"),
+        error
+    );
+}
@@ -1,4 +1,15 @@
+use std::net::SocketAddr;
+
+use crate::block_list::BlockReason;
+
 pub enum Response {
     Hit(Vec<u8>),
-    Miss(u16),
+    /// The query is to be forwarded, to the given upstream or else to the default one.
+    Miss(u16, Option<SocketAddr>),
+    /// Like `Miss`, but what is forwarded is the given query for another name.
+    Rewritten(u16, Vec<u8>, Option<SocketAddr>),
+    /// The encoded answer to a blocked query and the rule that blocked it.
+    Blocked(Vec<u8>, BlockReason),
+    /// The query is blocked and deliberately left unanswered.
+    Dropped(BlockReason),
 }